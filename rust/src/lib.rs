@@ -1,7 +1,13 @@
 mod solver;
 mod utils;
 
+use solver::algo::{SearchInfo, LOSS, WIN};
 use solver::board::*;
+use solver::config::*;
+use solver::mcts::*;
+use solver::notation::NotationError;
+use solver::record::{GameRecord, RecordError};
+use solver::rules::*;
 use solver::*;
 
 use wasm_bindgen::prelude::*;
@@ -26,6 +32,18 @@ pub struct State {
   mouse: Pos,
   selected_piece: Option<Pos>,
   selected_move: Option<Pos>,
+  /// First arrow of a move that shoots two.
+  selected_shot: Option<Pos>,
+  /// Rules for the next `new_game`.
+  rules: Rules,
+  /// Headers for `game_record`, such as the players.
+  headers: Vec<(String, String)>,
+  last_search: Option<SearchInfo>,
+  /// Move from `hint`, shown until the position changes.
+  hint: Option<Move>,
+  /// Position being set up by `begin_edit`, shown instead of the game.
+  editor: Option<Board>,
+  edit_tool: EditTool,
 }
 
 #[wasm_bindgen]
@@ -36,59 +54,552 @@ impl State {
       mouse: Pos { row: 0, col: 0 },
       selected_piece: None,
       selected_move: None,
+      selected_shot: None,
+      rules: Rules::default(),
+      headers: vec![],
+      last_search: None,
+      hint: None,
+      editor: None,
+      edit_tool: EditTool::Block,
     }
   }
 
   fn clear_selected(&mut self) {
     self.selected_piece = None;
     self.selected_move = None;
+    self.selected_shot = None;
+  }
+
+  /// Recommend a move for the side to move without playing it.
+  /// Its squares are marked by `token` until the position changes.
+  ///
+  /// Return undefined if there is no move.
+  pub fn hint(&mut self) -> Option<ReturnedMove> {
+    let info = self.gamestate.suggest_move()?;
+    let m: Move = info.best()?.clone().into();
+    self.hint = Some(m);
+    Some(m.into())
   }
 
   pub fn turn(&self) -> DrawableTeam {
     self.gamestate.turn.into()
   }
 
-  pub fn size(&self) -> usize {
+  /// The team that has won, or undefined while the game goes on.
+  /// Moves are refused once there is a winner.
+  pub fn winner(&self) -> Option<DrawableTeam> {
+    match self.gamestate.status() {
+      GameStatus::InProgress => None,
+      GameStatus::Winner(t) => Some(t.into()),
+    }
+  }
+
+  pub fn rows(&self) -> usize {
     // remember: 8x8 board is internally a 10x10 with walls around it
     // so -2 to give the illusion it's a clear 8x8
-    self.gamestate.current.size() - 2
+    self.gamestate.current.rows() - 2
+  }
+
+  pub fn cols(&self) -> usize {
+    self.gamestate.current.cols() - 2
   }
 
-  pub fn new_game(&mut self) {
-    self.gamestate.new_game();
+  /// Start a game on a board of `rows` by `cols` for `teams` teams,
+  /// 2 to 4, with `amazons` each, 2 or with two teams 4. The standard
+  /// game is 10, 10, 2 and 4. It is played by the rules from
+  /// `set_rules`.
+  ///
+  /// Return false, changing nothing, if there is no such setup.
+  pub fn new_game(&mut self, rows: usize, cols: usize, teams: usize, amazons: usize) -> bool {
+    if !self.gamestate.new_game(rows, cols, teams, amazons, self.rules) {
+      return false;
+    }
+    self.editor = None;
+    self.hint = None;
+    self.clear_selected();
     log(&self.gamestate.current.pprint());
+    true
+  }
+
+  /// Rules for games from `new_game`: how far pieces move and
+  /// arrows fly, 0 for any distance, and how many arrows, 0 to 2,
+  /// are shot each turn. The standard game is 0, 0 and 1.
+  ///
+  /// Return false, changing nothing, for more arrows than that or
+  /// ranges longer than the largest board.
+  pub fn set_rules(&mut self, move_range: u8, arrow_range: u8, arrows: usize) -> bool {
+    if arrows > MAX_ARROWS {
+      return false;
+    }
+    match Rules::from_bytes([move_range, arrow_range, arrows as u8]) {
+      Some(rules) => self.rules = rules,
+      None => return false,
+    }
+    true
+  }
+
+  /// Make a move for the side to move, returning undefined if
+  /// there is none.
+  pub fn ai_move(&mut self) -> Option<SearchReport> {
+    self.clear_selected();
+    self.hint = None;
+    self.last_search = self.gamestate.ai_move();
+    self.last_search()
   }
 
-  pub fn ai_move(&mut self) {
+  /// Play the game out from here, the AI settings playing Red and
+  /// Green against `opponent` playing Blue and Yellow, to compare
+  /// engines and strengths. Return the winner, or undefined if an
+  /// AI gave up or the game went on too long, as it may without
+  /// arrows.
+  pub fn self_play(&mut self, opponent: DifficultyLevel) -> Option<DrawableTeam> {
+    let config = *self.gamestate.config();
+    let opponent = EngineConfig { seed: config.seed, ..Difficulty::from(opponent).config() };
+    self.editor = None;
+    self.hint = None;
+    self.last_search = None;
     self.clear_selected();
-    self.gamestate.ai_move();
+    self.gamestate.self_play(&[config, opponent]).map(DrawableTeam::from)
+  }
+
+  /// The `k` best moves here as `SearchReport`s, best first,
+  /// without playing any of them.
+  pub fn analyze(&mut self, k: usize) -> Box<[JsValue]> {
+    self.gamestate
+      .analyze(k)
+      .iter()
+      .map(|info| JsValue::from(SearchReport::from(info)))
+      .collect()
+  }
+
+  /// What the search behind the last AI move found.
+  pub fn last_search(&self) -> Option<SearchReport> {
+    self.last_search.as_ref().map(SearchReport::from)
+  }
+
+  fn update_config(&mut self, f: impl FnOnce(&mut EngineConfig)) {
+    let mut config = *self.gamestate.config();
+    f(&mut config);
+    self.gamestate.set_config(config);
+  }
+
+  /// Deepest iteration `ai_move` may search.
+  pub fn set_max_depth(&mut self, depth: i32) {
+    self.update_config(|c| c.depth = depth);
+  }
+
+  /// Replies the alpha-beta search considers at each position.
+  pub fn set_beam_width(&mut self, width: usize) {
+    self.update_config(|c| c.beam_width = width.max(1));
+  }
+
+  /// Milliseconds `ai_move` may think, or `undefined` for no limit.
+  pub fn set_time_budget(&mut self, ms: Option<f64>) {
+    self.update_config(|c| c.time_ms = ms);
+  }
+
+  /// Positions `ai_move` may evaluate, or `undefined` for no limit.
+  pub fn set_node_budget(&mut self, nodes: Option<f64>) {
+    self.update_config(|c| c.nodes = nodes.map(|n| n.max(0.0) as u64));
+  }
+
+  /// Replace the AI settings with a preset strength, keeping the seed.
+  pub fn set_difficulty(&mut self, level: DifficultyLevel) {
+    self.update_config(|c| *c = EngineConfig { seed: c.seed, ..Difficulty::from(level).config() });
+  }
+
+  pub fn set_evaluator(&mut self, evaluator: EvaluatorChoice) {
+    self.update_config(|c| c.evaluator = evaluator.into());
+  }
+
+  /// Seed for the AI's random choices.
+  pub fn set_seed(&mut self, seed: f64) {
+    self.update_config(|c| c.seed = seed.max(0.0) as u64);
+  }
+
+  /// Set any engine option by name, see `EngineConfig::set_option`.
+  ///
+  /// Return false, logging why, if the name or value is not understood.
+  pub fn set_engine_option(&mut self, name: &str, value: &str) -> bool {
+    let mut config = *self.gamestate.config();
+    match config.set_option(name, value) {
+      Ok(()) => {
+        self.gamestate.set_config(config);
+        true
+      }
+      Err(msg) => {
+        log(&msg);
+        false
+      }
+    }
+  }
+
+  /// The AI settings as `name=value` options separated by spaces,
+  /// see `EngineConfig::options`, to hand to `Searcher.think`.
+  pub fn engine_options(&self) -> String {
+    self.gamestate.config()
+      .options()
+      .iter()
+      .map(|(name, value)| format!("{}={}", name, value))
+      .collect::<Vec<_>>()
+      .join(" ")
+  }
+
+  /// Start an AI move that is thought about a little at a time
+  /// by `ai_step`, for when a Web Worker is not available.
+  pub fn begin_ai_move(&mut self) {
+    self.clear_selected();
+    self.gamestate.start_search();
+  }
+
+  /// Think about the move from `begin_ai_move` for about `node_budget`
+  /// positions. Once the progress says it is done the move has been made.
+  ///
+  /// Return undefined if there is no AI move in progress.
+  pub fn ai_step(&mut self, node_budget: f64) -> Option<ThinkProgress> {
+    let p = self.gamestate.search_step(node_budget.max(1.0) as u64)?;
+    if p.done {
+      self.hint = None;
+      self.last_search = Some(p.info.clone());
+    }
+    Some(ThinkProgress {
+      nodes: p.info.nodes as f64,
+      depth: p.info.depth,
+      score: js_score(p.info.score),
+      done: p.done,
+    })
+  }
+
+  /// Use the alpha-beta search for `ai_move`.
+  pub fn use_min_max(&mut self) {
+    self.update_config(|c| c.engine = Engine::MinMax);
+  }
+
+  /// Use Monte Carlo tree search for `ai_move`, with random
+  /// playouts to the end of the game or short playouts cut off
+  /// by the evaluator. The time budget still applies.
+  pub fn use_mcts(&mut self, iterations: f64, random_playouts: bool) {
+    self.update_config(|c| {
+      c.engine = Engine::Mcts(MctsParams {
+        iterations: iterations.max(1.0) as u64,
+        playout: if random_playouts { Playout::Random } else { MctsParams::default().playout },
+        ..MctsParams::default()
+      })
+    });
+  }
+
+  /// Megabytes of memory for the search's transposition table.
+  pub fn set_hash_size(&mut self, megabytes: f64) {
+    self.gamestate.set_tt_memory((megabytes.max(0.0) * 1024.0 * 1024.0) as usize);
   }
 
+  /// Step back one move. It can be replayed with `redo`, and
+  /// stays as a variation if a different move is made.
   pub fn undo(&mut self) {
+    self.hint = None;
     self.gamestate.undo_move();
     self.clear_selected();
   }
 
+  /// Step forward along the main line.
+  pub fn redo(&mut self) {
+    self.hint = None;
+    self.gamestate.redo_move();
+    self.clear_selected();
+  }
+
+  /// Go to just after move `ply` of the current game, see `ply`.
+  pub fn goto_ply(&mut self, ply: usize) {
+    self.hint = None;
+    self.gamestate.goto_ply(ply);
+    self.clear_selected();
+  }
+
+  /// Go to the start of the current game.
+  pub fn first(&mut self) {
+    self.hint = None;
+    self.gamestate.first();
+    self.clear_selected();
+  }
+
+  /// Go to the end of the current game's main line.
+  pub fn last(&mut self) {
+    self.hint = None;
+    self.gamestate.last();
+    self.clear_selected();
+  }
+
+  /// Moves made so far in the current game.
+  pub fn ply(&self) -> usize {
+    self.gamestate.ply()
+  }
+
+  /// Moves on the current game's main line, counting undone
+  /// ones that can be redone.
+  pub fn game_length(&self) -> usize {
+    self.gamestate.game_length()
+  }
+
+  /// Moves tried from this position as `ReturnedMove`s,
+  /// the main line first.
+  pub fn variations(&self) -> Box<[JsValue]> {
+    self.gamestate
+      .variations()
+      .iter()
+      .map(|v| JsValue::from(ReturnedMove::from(v.mv)))
+      .collect()
+  }
+
+  /// Play variation `index` from `variations`.
+  pub fn play_variation(&mut self, index: usize) -> bool {
+    self.hint = None;
+    self.clear_selected();
+    self.gamestate.play_variation(index)
+  }
+
+  /// Make variation `index` from `variations` the main line.
+  pub fn promote_variation(&mut self, index: usize) -> bool {
+    self.gamestate.promote_variation(index)
+  }
+
+  /// Forget variation `index` from `variations` and everything after it.
+  pub fn delete_variation(&mut self, index: usize) -> bool {
+    self.gamestate.delete_variation(index)
+  }
+
+  /// Set up a position by clicking squares, starting from the one
+  /// on the board. The game is left alone until `start_from_edit`.
+  pub fn begin_edit(&mut self) {
+    self.clear_selected();
+    self.hint = None;
+    self.editor = Some(self.gamestate.current.clone());
+  }
+
+  /// Whether the board editor is open.
+  pub fn editing(&self) -> bool {
+    self.editor.is_some()
+  }
+
+  /// What clicking a square in the board editor puts there.
+  pub fn set_edit_tool(&mut self, tool: EditTool) {
+    self.edit_tool = tool;
+  }
+
+  /// Empty every square in the board editor, keeping its rules.
+  pub fn clear_edit(&mut self) {
+    if let Some(old) = self.editor.take() {
+      self.editor = Board::empty(self.rows(), self.cols()).map(|b| Board { rules: old.rules, ..b });
+    }
+  }
+
+  /// Why the position in the board editor cannot be played from,
+  /// or undefined if it can.
+  pub fn edit_error(&self) -> Option<String> {
+    let board = self.editor.as_ref()?;
+    board.check_position().err().map(|e| e.to_string())
+  }
+
+  /// Close the board editor and start a game from its position
+  /// with `first` to move. `undo` from there returns to the old game.
+  ///
+  /// Throw an error saying why, leaving the editor open, if the
+  /// position cannot be played from.
+  pub fn start_from_edit(&mut self, first: DrawableTeam) -> Result<(), JsValue> {
+    let turn = match first {
+      DrawableTeam::Red => Team::Red,
+      DrawableTeam::Blue => Team::Blue,
+      DrawableTeam::Green => Team::Green,
+      DrawableTeam::Yellow => Team::Yellow,
+      DrawableTeam::Gray => return Err(JsValue::from_str("gray is not a team")),
+    };
+    let board = match &self.editor {
+      Some(b) => b.clone(),
+      None => return Err(JsValue::from_str("the board editor is not open")),
+    };
+    self.gamestate
+      .start_position(board, turn, &[])
+      .map_err(|e| JsValue::from_str(&e.to_string()))?;
+    self.editor = None;
+    self.last_search = None;
+    Ok(())
+  }
+
+  /// Close the board editor, throwing its position away.
+  pub fn cancel_edit(&mut self) {
+    self.editor = None;
+  }
+
+  /// The position to hand to a `Searcher`, see `Amazons::position_bytes`.
+  pub fn position(&self) -> Vec<u8> {
+    self.gamestate.position_bytes()
+  }
+
+  /// The position as text, see `Position`, to save or share.
+  pub fn position_string(&self) -> String {
+    self.gamestate.position_string()
+  }
+
+  /// Start a game from the text of `position_string`. `undo` from
+  /// there returns to the old game.
+  ///
+  /// Throw an error saying why, changing nothing, if it cannot be
+  /// read or played from.
+  pub fn load_position(&mut self, position: &str) -> Result<(), JsValue> {
+    self.gamestate
+      .load_position(position)
+      .map_err(|e| JsValue::from_str(&e.to_string()))?;
+    self.editor = None;
+    self.hint = None;
+    self.last_search = None;
+    self.clear_selected();
+    Ok(())
+  }
+
+  /// Set a header for `game_record`, such as `Red` to a player's
+  /// name or `Date`. An empty `value` removes it.
+  ///
+  /// Return false, changing nothing, if `key` is not one word or is
+  /// one `game_record` fills in itself: `Size`, `Position` or `Result`.
+  pub fn set_record_header(&mut self, key: &str, value: &str) -> bool {
+    let fixed = ["Size", "Position", "Result"];
+    if key.is_empty() || !key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') || fixed.contains(&key) {
+      return false;
+    }
+    self.headers.retain(|(k, _)| k != key);
+    if !value.is_empty() {
+      self.headers.push((key.to_string(), value.to_string()));
+    }
+    true
+  }
+
+  /// The current game as text to save, see `GameRecord`.
+  pub fn game_record(&self) -> String {
+    self.gamestate.game_record(self.headers.clone()).to_string()
+  }
+
+  /// Start a game from the text of `game_record` and play its moves,
+  /// ending after the last. `undo` steps back through them and then
+  /// to the old game. The record's headers are kept for saving.
+  ///
+  /// Throw an error saying why, changing nothing, if the record
+  /// cannot be read, its moves are not legal or they do not end
+  /// with its result.
+  pub fn load_game_record(&mut self, text: &str) -> Result<(), JsValue> {
+    let record: GameRecord = text.parse().map_err(|e: RecordError| JsValue::from_str(&e.to_string()))?;
+    self.gamestate
+      .load_record(&record)
+      .map_err(|e| JsValue::from_str(&e.to_string()))?;
+    self.headers = record.headers;
+    self.editor = None;
+    self.hint = None;
+    self.last_search = None;
+    self.clear_selected();
+    Ok(())
+  }
+
+  /// Play a move found elsewhere, e.g. by a `Searcher` in a Web Worker,
+  /// given as `[piece_row, piece_col, move_row, move_col]` then the
+  /// row and column of each arrow, as `Searcher.think` does. Arrows
+  /// at row 0 are not shot.
+  ///
+  /// Throw an error saying why, changing nothing, if the move
+  /// is not legal here.
+  pub fn play_move(&mut self, mv: &[i8]) -> Result<(), JsValue> {
+    if mv.len() < 4 || mv.len() > 4 + 2 * MAX_ARROWS || !mv.len().is_multiple_of(2) {
+      return Err(JsValue::from_str("a move is four numbers and two per arrow"));
+    }
+
+    let mut shots: Shots = [None; MAX_ARROWS];
+    for (shot, at) in shots.iter_mut().zip(mv[4..].chunks(2)) {
+      if at[0] != 0 {
+        *shot = Some(Pos { row: at[0], col: at[1] });
+      }
+    }
+    self.play(Move {
+      old_pos: Pos { row: mv[0], col: mv[1] },
+      new_pos: Pos { row: mv[2], col: mv[3] },
+      shots,
+    })
+  }
+
+  /// Play a move written in notation, such as `d1-d7/g7`.
+  ///
+  /// Throw an error saying why, changing nothing, if it cannot
+  /// be read or is not legal here.
+  pub fn play_notation(&mut self, mv: &str) -> Result<(), JsValue> {
+    let mv: Move = mv.trim().parse().map_err(|e: NotationError| JsValue::from_str(&e.to_string()))?;
+    self.play(mv)
+  }
+
+  fn play(&mut self, mv: Move) -> Result<(), JsValue> {
+    if self.winner().is_some() {
+      return Err(JsValue::from_str("the game is over"));
+    }
+    self.gamestate
+      .player_move(mv)
+      .map_err(|e| JsValue::from_str(&e.to_string()))?;
+    self.clear_selected();
+    self.hint = None;
+    Ok(())
+  }
+
+  /// Play a move made by clicking, returning whether it was legal.
+  fn play_clicked(&mut self, piece: Pos, mv: Pos, shots: Shots) -> bool {
+    let played = self.gamestate.player_move(Move { old_pos: piece, new_pos: mv, shots });
+    if played.is_err() {
+      return false;
+    }
+    self.clear_selected();
+    self.hint = None;
+    true
+  }
+
   pub fn mouse_leave(&mut self) {
     self.clear_selected();
     self.mouse = Pos { row: -1, col: -1 };
   }
 
   pub fn mouse_click(&mut self, row: f64, col: f64) {
-    if !is_int_in_range(row, (1.0, self.size() as f64))
-    || !is_int_in_range(col, (1.0, self.size() as f64)) {
-    log(&format!("State.mouse_click({}, {}) out of [1, {}) range!",
-      row, col, self.size()));
+    if !is_int_in_range(row, (1.0, self.rows() as f64))
+    || !is_int_in_range(col, (1.0, self.cols() as f64)) {
+    log(&format!("State.mouse_click({}, {}) outside the {}x{} board!",
+      row, col, self.rows(), self.cols()));
+      // never take down the walls around the board
+      self.clear_selected();
+      return;
     }
 
     let clicked = Pos { row: row as i8, col: col as i8 };
+    if let Some(board) = &mut self.editor {
+      let placed = match self.edit_tool {
+        EditTool::Red => BoardSlot::Piece(Team::Red),
+        EditTool::Blue => BoardSlot::Piece(Team::Blue),
+        EditTool::Green => BoardSlot::Piece(Team::Green),
+        EditTool::Yellow => BoardSlot::Piece(Team::Yellow),
+        EditTool::Block => BoardSlot::Wall,
+        EditTool::Erase => BoardSlot::Empty,
+      };
+      // clicking again takes it away
+      let slot = board.at(clicked);
+      *slot = if *slot == placed { BoardSlot::Empty } else { placed };
+      return;
+    }
+    if self.winner().is_some() {
+      self.clear_selected();
+      return;
+    }
 
     match (self.selected_piece, self.selected_move) {
       (None, _) => {},
 
       (Some(piece),  None) => {
-        if self.gamestate.current.open_line_along(piece, clicked) {
-          self.selected_move = Some(clicked);
+        let board = &mut self.gamestate.current;
+        if board.rules.can_move(board, piece, clicked) {
+          if board.rules.arrows == 0 {
+            self.play_clicked(piece, clicked, [None; MAX_ARROWS]);
+          } else {
+            self.selected_move = Some(clicked);
+          }
           return;
         }
         if piece == clicked {
@@ -98,18 +609,25 @@ impl State {
       },
 
       (Some(piece), Some(mv)) => {
-        self.gamestate.current.swap_pos(piece, mv); // swap
-        if self.gamestate.current.open_line_along(mv, clicked) {
-          self.gamestate.current.swap_pos(piece, mv); // undo-swap
-          self.clear_selected();
-          self.gamestate.player_move(Move {
-            old_pos: piece,
-            new_pos: mv,
-            new_shot: clicked,
-          });
-          return;
+        if self.selected_shot.is_none() && self.gamestate.current.rules.arrows > 1 {
+          // the first of two arrows, shot from the moved piece
+          let board = &mut self.gamestate.current;
+          board.swap_pos(piece, mv);
+          let ok = board.rules.can_shoot(board, mv, clicked);
+          board.swap_pos(piece, mv);
+          if ok {
+            self.selected_shot = Some(clicked);
+            return;
+          }
+        } else {
+          let shots = match self.selected_shot {
+            Some(first) => [Some(first), Some(clicked)],
+            None => [Some(clicked), None],
+          };
+          if self.play_clicked(piece, mv, shots) {
+            return;
+          }
         }
-        self.gamestate.current.swap_pos(piece, mv); // undo-swap
       }
     };
 
@@ -122,24 +640,36 @@ impl State {
   }
 
   pub fn mouse_move(&mut self, row: f64, col: f64) {
-    if !is_int_in_range(row, (1.0, self.size() as f64))
-      || !is_int_in_range(col, (1.0, self.size() as f64)) {
-      log(&format!("State.mouse_move({}, {}) out of [1, {}) range!",
-        row, col, self.size()));
+    if !is_int_in_range(row, (1.0, self.rows() as f64))
+      || !is_int_in_range(col, (1.0, self.cols() as f64)) {
+      log(&format!("State.mouse_move({}, {}) outside the {}x{} board!",
+        row, col, self.rows(), self.cols()));
     }
     self.mouse.row = row as i8;
     self.mouse.col = col as i8;
   }
 
   pub fn token(&mut self, row: f64, col: f64) -> DrawableToken {
-    if !is_int_in_range(row, (1.0, self.size() as f64))
-      || !is_int_in_range(col, (1.0, self.size() as f64)) {
-      log(&format!("State.token({}, {}) out of [1, {}) range!",
-        row, col, self.size()));
+    if !is_int_in_range(row, (1.0, self.rows() as f64))
+      || !is_int_in_range(col, (1.0, self.cols() as f64)) {
+      log(&format!("State.token({}, {}) outside the {}x{} board!",
+        row, col, self.rows(), self.cols()));
     }
 
     let location = Pos { row: row as i8, col: col as i8 };
-    let mut dt = DrawableToken { wall: false, hover: false, piece: None};
+    let mut dt = DrawableToken { wall: false, hover: false, hint: false, piece: None};
+    if let Some(board) = &mut self.editor {
+      match board.at(location) {
+        BoardSlot::Empty => {},
+        BoardSlot::Wall => { dt.wall = true; },
+        BoardSlot::Piece(t) => { dt.piece = Some((*t).into()); },
+      };
+      dt.hover = location == self.mouse;
+      return dt;
+    }
+    if let Some(m) = &self.hint {
+      dt.hint = location == m.old_pos || location == m.new_pos || m.shots.contains(&Some(location));
+    }
 
 
     match (self.selected_piece, self.selected_move) {
@@ -154,7 +684,8 @@ impl State {
       (Some(piece),  None) => {
         match self.gamestate.current.at(location) {
           BoardSlot::Empty => {
-            if location == self.mouse && self.gamestate.current.open_line_along(piece, location) {
+            let board = &mut self.gamestate.current;
+            if location == self.mouse && board.rules.can_move(board, piece, location) {
               if let BoardSlot::Piece(t) = self.gamestate.current.at(piece) {
                 dt.piece = Some(t.clone().into());
               }
@@ -175,11 +706,15 @@ impl State {
 
       (Some(piece), Some(mv)) => {
 
-        self.gamestate.current.swap_pos(piece, mv);
+        let board = &mut self.gamestate.current;
+        board.swap_pos(piece, mv);
+        if let Some(shot) = self.selected_shot {
+          *board.at(shot) = BoardSlot::Wall;
+        }
 
-        match self.gamestate.current.at(location) {
+        match board.at(location) {
           BoardSlot::Empty => {
-            if location == self.mouse && self.gamestate.current.open_line_along(mv, location) {
+            if location == self.mouse && board.rules.can_shoot(board, mv, location) {
               dt.wall = true;
               dt.hover = true;
             } else if location == piece {
@@ -197,7 +732,10 @@ impl State {
           }
         };
 
-        self.gamestate.current.swap_pos(piece, mv);
+        if let Some(shot) = self.selected_shot {
+          *board.at(shot) = BoardSlot::Empty;
+        }
+        board.swap_pos(piece, mv);
       }
     };
 
@@ -206,16 +744,240 @@ impl State {
 }
 
 
+/// The AI on its own, to run inside a Web Worker so the page
+/// keeps drawing while it thinks. Positions come from
+/// `State.position()` and moves go back to `State.play_move()`.
+#[wasm_bindgen]
+pub struct Searcher {
+  gamestate: Amazons,
+  last_search: Option<SearchInfo>,
+  /// Calls to `think`, to vary the seed.
+  thoughts: u64,
+}
+
+#[wasm_bindgen]
+impl Searcher {
+  pub fn new() -> Searcher {
+    Searcher {
+      gamestate: Amazons::new(),
+      last_search: None,
+      thoughts: 0,
+    }
+  }
+
+  /// Move in `position` with the AI settings from
+  /// `State.engine_options()` as `[piece_row, piece_col, move_row,
+  /// move_col, shot_row, shot_col, shot2_row, shot2_col]`, with 0 for
+  /// arrows not shot, or empty if there is none.
+  pub fn think(&mut self, position: &[u8], options: &str) -> Vec<i8> {
+    if !self.gamestate.load_position_bytes(position) {
+      log("Searcher.think given a malformed position");
+      return vec![];
+    }
+    let mut config = EngineConfig::default();
+    for option in options.split_whitespace() {
+      let mut parts = option.splitn(2, '=');
+      let (name, value) = (parts.next().unwrap(), parts.next().unwrap_or(""));
+      if let Err(msg) = config.set_option(name, value) {
+        log(&msg);
+        return vec![];
+      }
+    }
+    // a new seed each time, so weak levels do not repeat their mistakes
+    config.seed = config.seed.wrapping_add(self.thoughts);
+    self.thoughts += 1;
+    self.gamestate.set_config(config);
+
+    self.last_search = self.gamestate.ai_move();
+    match &self.last_search {
+      Some(info) => move_bytes(&info.best().unwrap().clone().into()),
+      None => vec![],
+    }
+  }
+
+  /// What the last `think` found.
+  pub fn last_search(&self) -> Option<SearchReport> {
+    self.last_search.as_ref().map(SearchReport::from)
+  }
+}
+
+impl Default for Searcher {
+  fn default() -> Searcher {
+    Searcher::new()
+  }
+}
+
+fn move_bytes(m: &Move) -> Vec<i8> {
+  let mut v = vec![
+    m.old_pos.row, m.old_pos.col,
+    m.new_pos.row, m.new_pos.col,
+  ];
+  for shot in m.shots.iter() {
+    let at = shot.unwrap_or(Pos { row: 0, col: 0 });
+    v.extend(&[at.row, at.col]);
+  }
+  v
+}
+
+/// Scores for JS, with won and lost positions as infinities.
+fn js_score(score: i64) -> f64 {
+  match score {
+    WIN => f64::INFINITY,
+    LOSS => f64::NEG_INFINITY,
+    _ => score as f64,
+  }
+}
+
+/// Statistics and principal variation of a search, see `SearchInfo`.
+#[wasm_bindgen]
+#[derive(Clone, Debug)]
+pub struct SearchReport {
+  pub nodes: f64,
+  pub time_ms: f64,
+  /// Deepest iteration completed.
+  pub depth: i32,
+  /// Score of the chosen move for the side that made it.
+  pub score: f64,
+  pv: Vec<i8>,
+  line: String,
+}
+
+#[wasm_bindgen]
+impl SearchReport {
+  /// The chosen move and expected replies, eight numbers per move
+  /// in the same layout as `Searcher.think`.
+  pub fn pv(&self) -> Vec<i8> {
+    self.pv.clone()
+  }
+
+  /// The same moves in notation, separated by spaces.
+  pub fn line(&self) -> String {
+    self.line.clone()
+  }
+}
+
+impl From<&SearchInfo> for SearchReport {
+  fn from(info: &SearchInfo) -> SearchReport {
+    SearchReport {
+      nodes: info.nodes as f64,
+      time_ms: info.time_ms,
+      depth: info.depth,
+      score: js_score(info.score),
+      pv: info.pv.iter().flat_map(|m| move_bytes(&m.clone().into())).collect(),
+      line: info.pv.iter().map(|m| Move::from(m.clone()).to_string()).collect::<Vec<_>>().join(" "),
+    }
+  }
+}
+
+/// How far `State.ai_step` has got.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug)]
+pub struct ThinkProgress {
+  pub nodes: f64,
+  /// Deepest search completed so far.
+  pub depth: i32,
+  /// Score of the best move so far, for the side to move.
+  pub score: f64,
+  pub done: bool,
+}
+
 #[wasm_bindgen]
 #[derive(Clone, Copy, Debug)]
 pub enum DrawableTeam {
-  Red, Blue, Gray,
+  Red, Blue, Gray, Green, Yellow,
 }
 impl From<Team> for DrawableTeam {
   fn from(t: Team) -> DrawableTeam {
     match t {
       Team::Red => DrawableTeam::Red,
       Team::Blue => DrawableTeam::Blue,
+      Team::Green => DrawableTeam::Green,
+      Team::Yellow => DrawableTeam::Yellow,
+    }
+  }
+}
+
+/// What a click in the board editor puts on a square.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug)]
+pub enum EditTool {
+  Red, Blue, Block, Erase, Green, Yellow,
+}
+
+/// A move in board coordinates, as used by `State.token`. Arrows
+/// not shot are at 0, 0.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug)]
+pub struct ReturnedMove {
+  pub piece_y: i8,
+  pub piece_x: i8,
+  pub move_y: i8,
+  pub move_x: i8,
+  pub stone_y: i8,
+  pub stone_x: i8,
+  pub stone2_y: i8,
+  pub stone2_x: i8,
+}
+impl From<Move> for ReturnedMove {
+  fn from(m: Move) -> ReturnedMove {
+    let none = Pos { row: 0, col: 0 };
+    let (stone, stone2) = (m.shots[0].unwrap_or(none), m.shots[1].unwrap_or(none));
+    ReturnedMove {
+      piece_y: m.old_pos.row,
+      piece_x: m.old_pos.col,
+      move_y: m.new_pos.row,
+      move_x: m.new_pos.col,
+      stone_y: stone.row,
+      stone_x: stone.col,
+      stone2_y: stone2.row,
+      stone2_x: stone2.col,
+    }
+  }
+}
+
+#[wasm_bindgen]
+impl ReturnedMove {
+  /// The move in notation, such as `d1-d7/g7`.
+  pub fn notation(&self) -> String {
+    let at = |row, col| if row == 0 { None } else { Some(Pos { row, col }) };
+    let mv = Move {
+      old_pos: Pos { row: self.piece_y, col: self.piece_x },
+      new_pos: Pos { row: self.move_y, col: self.move_x },
+      shots: [at(self.stone_y, self.stone_x), at(self.stone2_y, self.stone2_x)],
+    };
+    mv.to_string()
+  }
+}
+
+/// Preset AI strengths, see `Difficulty`.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug)]
+pub enum DifficultyLevel {
+  Beginner, Easy, Medium, Hard, Expert,
+}
+impl From<DifficultyLevel> for Difficulty {
+  fn from(d: DifficultyLevel) -> Difficulty {
+    match d {
+      DifficultyLevel::Beginner => Difficulty::Beginner,
+      DifficultyLevel::Easy => Difficulty::Easy,
+      DifficultyLevel::Medium => Difficulty::Medium,
+      DifficultyLevel::Hard => Difficulty::Hard,
+      DifficultyLevel::Expert => Difficulty::Expert,
+    }
+  }
+}
+
+/// How the AI scores positions, see `Evaluator`.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug)]
+pub enum EvaluatorChoice {
+  Territory, Endgame,
+}
+impl From<EvaluatorChoice> for Evaluator {
+  fn from(e: EvaluatorChoice) -> Evaluator {
+    match e {
+      EvaluatorChoice::Territory => Evaluator::Territory,
+      EvaluatorChoice::Endgame => Evaluator::Endgame,
     }
   }
 }
@@ -225,6 +987,8 @@ impl From<Team> for DrawableTeam {
 pub struct DrawableToken {
   pub wall: bool,
   pub hover: bool,
+  /// Part of the move from `State.hint`.
+  pub hint: bool,
   pub piece: Option<DrawableTeam>,
 }
 
@@ -238,97 +1002,3 @@ fn is_int_in_range(val: f64, range: (f64, f64)) -> bool {
   }
   return true;
 }
-
-
-// #[wasm_bindgen]
-// pub struct RequestedBoard {
-//   pub size: f64,
-//   blocks: Vec<(f64, f64)>,
-//   red_team: Vec<(f64, f64)>,
-//   blue_team: Vec<(f64, f64)>,
-// }
-
-// #[wasm_bindgen]
-// impl RequestedBoard {
-//   pub fn add_red_team(&mut self, y: f64, x: f64) {
-//     self.red_team.push((y, x));
-//   }
-//   pub fn add_blue_team(&mut self, y: f64, x: f64) {
-//     self.blue_team.push((y, x));
-//   }
-//   pub fn add_block(&mut self, y: f64, x: f64) {
-//     self.blocks.push((y, x));
-//   }
-
-//   pub fn new(size: f64) -> RequestedBoard {
-//     RequestedBoard {
-//       size: size,
-//       red_team: Vec::new(),
-//       blue_team: Vec::new(),
-//       blocks: Vec::new(),
-//     }
-//   }
-
-//   pub fn is_valid(&self) -> bool {
-//     // [1, width]  [1, height]
-//     if !is_int_in_range(self.size, (1.0, 10.0)) {
-//       return false;
-//     }
-
-//     for &(y, x) in &self.red_team {
-//       if !is_int_in_range(y, (1.0, self.size)) || !is_int_in_range(x, (1.0, self.size)) {
-//         return false;
-//       }
-//     }
-
-//     for &(y, x) in &self.blue_team {
-//       if !is_int_in_range(y, (1.0, self.size)) || !is_int_in_range(x, (1.0, self.size)) {
-//         return false;
-//       }
-//     }
-
-//     for &(y, x) in &self.blocks {
-//       if !is_int_in_range(y, (1.0, self.size)) || !is_int_in_range(x, (1.0, self.size)) {
-//         return false;
-//       }
-//     }
-
-//     return true;
-//   }
-// }
-
-// #[wasm_bindgen]
-// pub fn compute_ai_move(rb: &RequestedBoard) -> ReturnedMove {
-//   let mut players = Vec::new();
-//   for &(y,x) in &rb.blue_team {
-//     players.push(Player{ team:Team::Blue, pos:Pos {row:  y as i8, col:  x as i8} });
-//   }
-//   for &(y,x) in &rb.red_team {
-//     players.push(Player{ team:Team::Red, pos:Pos {row:  y as i8, col:  x as i8} });
-//   }
-//   let mut board = CompactBoard::new(rb.size as i8 + 2, players);
-//   for &(y,x) in &rb.blocks {
-//     board.wall_set(Pos {row:y as i8,col:x as i8}, true);
-//   }
-
-//   let mut amazon = Amazons::from_board(board);
-
-//   if let Some(cm) = amazon.ai_move(Team::Red) {
-//     // log(&amazon.nth_last_board(1).pprint());
-//     // log(&amazon.nth_last_board(0).pprint());
-//     return ReturnedMove {
-//       piece_y: cm.old_pos.row,
-//       piece_x: cm.old_pos.col,
-//       move_y: cm.new_pos.row,
-//       move_x: cm.new_pos.col,
-//       stone_y: cm.new_shot.row,
-//       stone_x: cm.new_shot.col,
-//     };
-//   } else {
-//     return ReturnedMove {
-//       piece_y: 0, piece_x: 0,
-//       move_y: 0, move_x: 0,
-//       stone_y: 0, stone_x: 0,
-//     };
-//   }
-// }
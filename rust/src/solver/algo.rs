@@ -1,63 +1,780 @@
 use crate::board::*;
 use crate::compact_board::*;
+use crate::config::*;
+use crate::transposition::*;
+use crate::utils::now_ms;
 use smallvec::SmallVec;
+use std::sync::atomic::{AtomicU64, Ordering};
 
-pub fn min_max(cache: &mut DistState, board: &CompactBoard, team: Team, depth: i32) -> (Option<CompactMove>, i64) {
-  let mut local_board = board.clone();
-
-  if depth <= 1 {
-    let best = board.successors(team)
-      .map(|mv| {
-        local_board.apply_move(&mv);
-        let eval = evaluate_by_queen_bfs_distance(&local_board, team.other(), cache);
-        local_board.un_apply_move(&mv);
-        (eval, mv)
-      })
-      .min_by_key(|it| it.0);
-    if let Some((score, mv)) = best {
-      return (Some(mv), score);
-    } else {
-      return (None, i64::min_value() + 1);
+/// Score of a won position, from the winner's perspective.
+pub const WIN: i64 = i64::MAX;
+/// Score of a lost position. Negating it gives `WIN`.
+pub const LOSS: i64 = i64::MIN + 1;
+
+/// What a search found, and how hard it looked.
+#[derive(Clone, Debug)]
+pub struct SearchInfo {
+  /// Principal variation: the chosen move followed by the replies
+  /// the search expects. Empty if there is no move.
+  pub pv: Vec<CompactMove>,
+  /// Score of the first move, for the team making it.
+  pub score: i64,
+  /// Deepest iteration that completed.
+  pub depth: i32,
+  /// Positions evaluated.
+  pub nodes: u64,
+  pub time_ms: f64,
+}
+
+impl SearchInfo {
+  pub fn best(&self) -> Option<&CompactMove> {
+    self.pv.first()
+  }
+}
+
+/// Search depth 1, 2, 3, ... until `config`'s budget runs out, and return
+/// the result of the deepest iteration that completed.
+///
+/// `tt` is consulted and filled if given; it may hold results
+/// from earlier searches.
+pub fn iterative_deepening(cache: &mut DistState, tt: Option<&mut TranspositionTable>, board: &CompactBoard, team: Team, config: EngineConfig) -> SearchInfo {
+  let mut search = Search::new(cache, tt, config);
+  let mut deepening = Deepening::new(board, team, config);
+  deepening.run(&mut search, None);
+
+  let mut result = deepening.result;
+  result.nodes = search.nodes;
+  result.time_ms = now_ms() - search.start_ms;
+  result
+}
+
+/// Exact scores for the `k` best root moves, best first, by iterative
+/// deepening within `config`'s budget like `iterative_deepening`.
+///
+/// Candidates are the `k` or `beam_width` statically best moves,
+/// whichever is more, each searched with a full window. Every entry
+/// reports the nodes and time of the whole analysis.
+pub fn multi_pv(cache: &mut DistState, tt: Option<&mut TranspositionTable>, board: &CompactBoard, team: Team, config: EngineConfig, k: usize) -> Vec<SearchInfo> {
+  let mut search = Search::new(cache, tt, config);
+  let mut stack = Stack::new(board);
+  let mut candidates: Vec<CompactMove> = top_n(config.beam_width.max(k), board.successors(team).map(|m| {
+    stack.board.apply_move(&m);
+    let eval = search.evaluate(&stack.board, team);
+    stack.board.un_apply_move(&m);
+    (eval, m)
+  })).into_iter().map(|(_, m)| m).collect();
+
+  let mut result: Vec<SearchInfo> = vec![];
+  for depth in 1..=config.depth.max(1) {
+    search.can_abort = depth > 1;
+    let mut found = vec![];
+    for mv in &candidates {
+      stack.board.apply_move(mv);
+      let score = if depth <= 1 {
+        stack.start_pv(0);
+        -search.evaluate(&stack.board, team.other())
+      } else {
+        -search.alpha_beta(&mut stack, team.other(), depth - 1, LOSS, WIN, 1).1
+      };
+      stack.board.un_apply_move(mv);
+      if search.aborted {
+        break;
+      }
+      stack.update_pv(0, mv.clone());
+      found.push(SearchInfo { pv: stack.pv[0].clone(), score, depth, nodes: 0, time_ms: 0.0 });
+    }
+    if search.aborted {
+      break;
     }
+
+    // stable, so ties keep the previous ranking
+    found.sort_by_key(|info| std::cmp::Reverse(info.score));
+    candidates = found.iter().map(|info| info.pv[0].clone()).collect();
+    result = found;
+  }
+
+  result.truncate(k);
+  for info in &mut result {
+    info.nodes = search.nodes;
+    info.time_ms = now_ms() - search.start_ms;
   }
+  result
+}
 
-  let mut best: Option<CompactMove> = None;
-  let mut score: i64 = i64::min_value() + 1;
+/// What a `SteppedSearch` has found so far.
+#[derive(Clone, Debug)]
+pub struct StepProgress {
+  /// The deepest completed iteration, or what the first one has
+  /// seen so far with depth 0.
+  pub info: SearchInfo,
+  pub done: bool,
+}
 
-  let top_boards = top_n(board.successors(team).map(|m| {
-    local_board.apply_move(&m);
-    let eval = evaluate_by_queen_bfs_distance(&local_board, team, cache);
-    local_board.un_apply_move(&m);
-    return (eval, m);
-  }));
+/// `iterative_deepening` that can be paused and resumed, for
+/// single-threaded wasm where the page must keep drawing.
+pub struct SteppedSearch {
+  cache: DistState,
+  config: EngineConfig,
+  deepening: Deepening,
+  start_ms: f64,
+  nodes: u64,
+}
 
-  for (_, b) in top_boards {
-    local_board.apply_move(&b);
-    let (_, resp_score) = min_max(cache, &local_board, team.other(), depth - 1);
-    local_board.un_apply_move(&b);
+impl SteppedSearch {
+  pub fn new(board: &CompactBoard, team: Team, config: EngineConfig) -> SteppedSearch {
+    SteppedSearch {
+      cache: DistState::new(),
+      config,
+      deepening: Deepening::new(board, team, config),
+      start_ms: now_ms(),
+      nodes: 0,
+    }
+  }
 
-    if score < -resp_score {
-      score = -resp_score;
-      best = Some(b);
+  pub fn progress(&self) -> StepProgress {
+    let deepening = &self.deepening;
+    let (pv, score) = match deepening.stack.frames.first() {
+      Some(root) if deepening.result.depth == 0 => (deepening.stack.pv[0].clone(), root.score),
+      _ => (deepening.result.pv.clone(), deepening.result.score),
+    };
+    StepProgress {
+      info: SearchInfo {
+        pv,
+        score,
+        depth: deepening.result.depth,
+        nodes: self.nodes,
+        time_ms: now_ms() - self.start_ms,
+      },
+      done: deepening.done,
     }
   }
 
-  match best {
-    None => min_max(cache, board, team, 1),
-    _ => (best, score),
+  /// Evaluate about `node_budget` more positions, then report.
+  /// `tt` is used as by `iterative_deepening`.
+  pub fn search_step(&mut self, tt: Option<&mut TranspositionTable>, node_budget: u64) -> StepProgress {
+    let mut search = Search::new(&mut self.cache, tt, self.config);
+    search.start_ms = self.start_ms;
+    search.nodes = self.nodes;
+    self.deepening.run(&mut search, Some(self.nodes + node_budget.max(1)));
+    self.nodes = search.nodes;
+    self.progress()
   }
 }
 
-fn top_n<A>(iter: impl Iterator<Item = (i64, A)>) -> SmallVec<[(i64, A); 15]> {
-  let mut vec = SmallVec::<[(i64, A); 15]>::new();
+/// What one thread of `parallel_iterative_deepening` found
+/// in its share of the root moves.
+#[cfg(not(target_arch = "wasm32"))]
+struct RootShare {
+  /// Index into the root moves of the best, if any was searched.
+  best: Option<usize>,
+  score: i64,
+  pv: Vec<CompactMove>,
+  nodes: u64,
+  aborted: bool,
+}
+
+/// `iterative_deepening` with the root moves of each iteration dealt
+/// out across one thread per cache. Threads search their share
+/// independently, so the result does not depend on timing: the best
+/// score wins, and ties go to the move earliest in the root ordering,
+/// as in the single-threaded search.
+///
+/// `tt` is used by the first iteration and by the first thread, which
+/// searches the previous best move, so that only one thread touches it.
+/// The node budget counts every thread's nodes over all iterations.
+///
+/// Not available on wasm, which has no threads.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn parallel_iterative_deepening(caches: &mut [DistState], mut tt: Option<&mut TranspositionTable>, board: &CompactBoard, team: Team, config: EngineConfig) -> SearchInfo {
+  if caches.len() <= 1 || config.depth <= 1 {
+    return iterative_deepening(&mut caches[0], tt, board, team, config);
+  }
+
+  let start_ms = now_ms();
+  let threads = caches.len();
+  let mut result = iterative_deepening(&mut caches[0], tt.as_deref_mut(), board, team, EngineConfig { depth: 1, ..config });
+
+  let beam: Vec<CompactMove> = {
+    let mut search = Search::new(&mut caches[0], None, config);
+    search.nodes = result.nodes;
+    let mut local_board = board.clone();
+    let top = top_n(config.beam_width, board.successors(team).map(|m| {
+      local_board.apply_move(&m);
+      let eval = search.evaluate(&local_board, team);
+      local_board.un_apply_move(&m);
+      (eval, m)
+    }));
+    result.nodes = search.nodes;
+    top.into_iter().map(|(_, m)| m).collect()
+  };
+
+  let counted = AtomicU64::new(result.nodes);
+  for depth in 2..=config.depth {
+    if result.pv.is_empty() || result.score == WIN || result.score == LOSS {
+      break;
+    }
+
+    // previous best first, like the root hint of the sequential search
+    let mut root = beam.clone();
+    if let Some(prev) = result.best() {
+      match root.iter().position(|m| m == prev) {
+        Some(i) => {
+          let it = root.remove(i);
+          root.insert(0, it);
+        }
+        None => root.insert(0, prev.clone()),
+      }
+    }
+
+    let mut thread_config = config;
+    thread_config.time_ms = config.time_ms.map(|t| t - (now_ms() - start_ms));
+
+    let mut first_tt = tt.as_deref_mut();
+    let found: Vec<RootShare> = std::thread::scope(|scope| {
+      let (root, counted) = (&root, &counted);
+      let handles: Vec<_> = caches.iter_mut().enumerate().map(|(t, cache)| {
+        let tt = if t == 0 { first_tt.take() } else { None };
+        scope.spawn(move || {
+          let mut search = Search::new(cache, tt, thread_config);
+          search.shared_nodes = Some(counted);
+          search.can_abort = true;
+          let share: Vec<usize> = (t..root.len()).step_by(threads).collect();
+          let (best, score, pv) = search.search_root_moves(board, team, depth, root, &share);
+          RootShare { best, score, pv, nodes: search.nodes, aborted: search.aborted }
+        })
+      }).collect();
+      handles.into_iter().map(|h| h.join().unwrap()).collect()
+    });
+
+    result.nodes += found.iter().map(|f| f.nodes).sum::<u64>();
+    if found.iter().any(|f| f.aborted) {
+      break;
+    }
+    let best = found.iter()
+      .filter_map(|f| f.best.map(|ix| (ix, f.score, &f.pv)))
+      .max_by(|a, b| a.1.cmp(&b.1).then(b.0.cmp(&a.0)));
+    match best {
+      Some((_, score, pv)) => {
+        result.pv = pv.clone();
+        result.score = score;
+        result.depth = depth;
+      }
+      None => {
+        let nodes = result.nodes;
+        result = iterative_deepening(&mut caches[0], tt.as_deref_mut(), board, team, EngineConfig { depth: 1, ..config });
+        counted.fetch_add(result.nodes, Ordering::Relaxed);
+        result.nodes += nodes;
+      }
+    }
+  }
+
+  result.time_ms = now_ms() - start_ms;
+  result
+}
+
+/// Iterative deepening from one position, run all at once by
+/// `iterative_deepening` and a few nodes at a time by `SteppedSearch`.
+struct Deepening {
+  team: Team,
+  max_depth: i32,
+  stack: Stack,
+  /// The deepest completed iteration.
+  result: SearchInfo,
+  done: bool,
+}
+
+impl Deepening {
+  fn new(board: &CompactBoard, team: Team, config: EngineConfig) -> Deepening {
+    Deepening {
+      team,
+      max_depth: config.depth.max(1),
+      stack: Stack::new(board),
+      result: SearchInfo { pv: vec![], score: LOSS, depth: 0, nodes: 0, time_ms: 0.0 },
+      done: false,
+    }
+  }
+
+  /// Search until the last iteration completes or the budget runs
+  /// out, or pause once `search.nodes` reaches `stop_at`.
+  fn run(&mut self, search: &mut Search, stop_at: Option<u64>) {
+    while !self.done {
+      let depth = self.result.depth + 1;
+      search.can_abort = depth > 1;
+      if self.stack.frames.is_empty() {
+        self.stack.root_hint = self.result.best().cloned();
+        // the table is not used to cut off the root, so this
+        // always leaves it on the stack
+        search.enter(&mut self.stack, self.team, depth, LOSS, WIN, 0);
+      }
+      let score = match search.run(&mut self.stack, stop_at) {
+        Some((_, score)) => score,
+        None => return,
+      };
+      if search.aborted {
+        self.done = true;
+        return;
+      }
+      self.result.pv = self.stack.pv[0].clone();
+      self.result.score = score;
+      self.result.depth = depth;
+      self.done = depth >= self.max_depth || self.result.pv.is_empty() || score == WIN || score == LOSS;
+    }
+  }
+}
+
+/// Where a `Frame` is in searching its position.
+#[derive(Clone, Copy, Debug)]
+enum Phase {
+  /// Scoring `moves[i]` to choose the beam.
+  Ordering(usize),
+  /// Waiting on the reply to `moves[i]`, which is applied to the board.
+  Searching(usize),
+  /// Scoring `moves[i]` directly, at depth 1 or when the beam
+  /// had nothing to pick.
+  Leaf(usize),
+  Finished,
+}
+
+/// One node of the alpha-beta search, holding what a recursive
+/// search would keep in its locals.
+struct Frame {
+  team: Team,
+  depth: i32,
+  /// Distance from the root of the search.
+  ply: u32,
+  /// Transposition table key.
+  key: u64,
+  /// `alpha` as the node was entered, to tell the table which
+  /// bound the score is.
+  first_alpha: i64,
+  alpha: i64,
+  beta: i64,
+  /// Move to search first.
+  hint: Option<CompactMove>,
+  /// Whether `moves` are scored directly instead of searched.
+  leaf: bool,
+  moves: Vec<CompactMove>,
+  beam: SmallVec<[(i64, CompactMove); 15]>,
+  phase: Phase,
+  best: Option<CompactMove>,
+  score: i64,
+}
+
+/// The nodes an alpha-beta search is inside, the root first. Kept
+/// on the heap instead of in recursion, so that `Search::run` can
+/// stop between any two positions and carry on later.
+struct Stack {
+  /// The position of the last node.
+  board: CompactBoard,
+  frames: Vec<Frame>,
+  /// Move to try first at the root.
+  root_hint: Option<CompactMove>,
+  /// `pv[ply]` is the best line found from the node being
+  /// searched at `ply`.
+  pv: Vec<Vec<CompactMove>>,
+}
+
+impl Stack {
+  fn new(board: &CompactBoard) -> Stack {
+    Stack {
+      board: board.clone(),
+      frames: vec![],
+      root_hint: None,
+      pv: vec![],
+    }
+  }
+
+  /// Forget the lines at `ply` and below, before searching there.
+  fn start_pv(&mut self, ply: u32) {
+    let ply = ply as usize;
+    if self.pv.len() < ply + 2 {
+      self.pv.resize(ply + 2, vec![]);
+    }
+    self.pv[ply].clear();
+    self.pv[ply + 1].clear();
+  }
 
-  iter.for_each(|new| {
-    match vec.binary_search_by_key(&-new.0, |a| -a.0) {
-      Ok(i) => vec.insert(i, new),
-      Err(i) => vec.insert(i, new),
+  /// `mv` is the new best move at `ply`, followed by the line
+  /// just found after it.
+  fn update_pv(&mut self, ply: u32, mv: CompactMove) {
+    let (head, tail) = self.pv.split_at_mut(ply as usize + 1);
+    let line = &mut head[ply as usize];
+    line.clear();
+    line.push(mv);
+    line.extend_from_slice(&tail[0]);
+  }
+
+  /// The reply to the move the last node is searching scored
+  /// `score`: take the move back and see if it is the best yet.
+  fn reply_searched(&mut self, score: i64) {
+    let top = self.frames.len() - 1;
+    let i = match self.frames[top].phase {
+      Phase::Searching(i) => i,
+      _ => unreachable!(),
+    };
+    let mv = self.frames[top].moves[i].clone();
+    self.board.un_apply_move(&mv);
+
+    let frame = &mut self.frames[top];
+    if frame.score < -score {
+      frame.score = -score;
+      frame.best = Some(mv.clone());
+      let ply = frame.ply;
+      self.update_pv(ply, mv);
+    }
+    let frame = &mut self.frames[top];
+    if frame.alpha < frame.score {
+      frame.alpha = frame.score;
+    }
+    frame.phase = if frame.alpha >= frame.beta { Phase::Finished } else { Phase::Searching(i + 1) };
+  }
+}
+
+/// Scratch state shared by every node of one search, and the
+/// tables it borrows.
+struct Search<'a> {
+  cache: &'a mut DistState,
+  tt: Option<&'a mut TranspositionTable>,
+  config: EngineConfig,
+  /// `config.evaluation_key()`, mixed into every table key.
+  evaluation_key: u64,
+  start_ms: f64,
+  nodes: u64,
+  /// Nodes of every thread of a parallel search, this one's
+  /// included, for the node budget.
+  shared_nodes: Option<&'a AtomicU64>,
+  can_abort: bool,
+  aborted: bool,
+}
+
+impl<'a> Search<'a> {
+  fn new(cache: &'a mut DistState, tt: Option<&'a mut TranspositionTable>, config: EngineConfig) -> Search<'a> {
+    Search {
+      cache,
+      tt,
+      config,
+      evaluation_key: config.evaluation_key(),
+      start_ms: now_ms(),
+      nodes: 0,
+      shared_nodes: None,
+      can_abort: false,
+      aborted: false,
+    }
+  }
+
+  fn evaluate(&mut self, board: &CompactBoard, team: Team) -> i64 {
+    self.nodes += 1;
+    if let Some(shared) = self.shared_nodes {
+      shared.fetch_add(1, Ordering::Relaxed);
+    }
+    if self.can_abort && !self.aborted && self.nodes.is_multiple_of(64) {
+      self.aborted = self.out_of_budget();
+    }
+    self.config.evaluate(board, team, self.cache)
+  }
+
+  fn out_of_budget(&self) -> bool {
+    if let Some(nodes) = self.config.nodes {
+      let counted = self.shared_nodes.map_or(self.nodes, |n| n.load(Ordering::Relaxed));
+      if counted >= nodes {
+        return true;
+      }
+    }
+    if let Some(time_ms) = self.config.time_ms {
+      if now_ms() - self.start_ms >= time_ms {
+        return true;
+      }
+    }
+    false
+  }
+
+  /// The root loop of the alpha-beta search over `moves[i]` for
+  /// each `i` in `share`, returning the index of the best one and
+  /// its line.
+  fn search_root_moves(&mut self, board: &CompactBoard, team: Team, depth: i32, moves: &[CompactMove], share: &[usize]) -> (Option<usize>, i64, Vec<CompactMove>) {
+    let mut stack = Stack::new(board);
+    stack.start_pv(0);
+    let mut best: Option<usize> = None;
+    let mut score = LOSS;
+    for &i in share {
+      if self.aborted {
+        break;
+      }
+      stack.board.apply_move(&moves[i]);
+      let (_, resp_score) = self.alpha_beta(&mut stack, team.other(), depth - 1, -WIN, -score, 1);
+      stack.board.un_apply_move(&moves[i]);
+
+      if score < -resp_score {
+        score = -resp_score;
+        best = Some(i);
+        stack.update_pv(0, moves[i].clone());
+      }
     }
-    vec.truncate(14)
-  });
+    (best, score, stack.pv.swap_remove(0))
+  }
 
+  /// Fail-soft alpha-beta from `stack.board`, which must have no
+  /// node searching it yet, over the `beam_width` best replies by
+  /// static evaluation.
+  ///
+  /// The returned score is exact when it lies strictly inside
+  /// `(alpha, beta)`, otherwise it is a bound on the true score.
+  /// `ply` is the distance from the root of the search.
+  fn alpha_beta(&mut self, stack: &mut Stack, team: Team, depth: i32, alpha: i64, beta: i64, ply: u32) -> (Option<CompactMove>, i64) {
+    match self.enter(stack, team, depth, alpha, beta, ply) {
+      Some(result) => result,
+      None => self.run(stack, None).unwrap(),
+    }
+  }
+
+  /// Start a node searching `stack.board` for `team`, after the
+  /// last one. Return its result instead if the table has it.
+  fn enter(&mut self, stack: &mut Stack, team: Team, depth: i32, alpha: i64, beta: i64, ply: u32) -> Option<(Option<CompactMove>, i64)> {
+    let key = stack.board.hash() ^ zobrist_turn(team) ^ self.evaluation_key;
+    stack.start_pv(ply);
+
+    let mut hint = if ply == 0 { stack.root_hint.clone() } else { None };
+    if let Some(entry) = self.tt.as_ref().and_then(|tt| tt.probe(key)) {
+      if ply > 0 && entry.depth >= depth {
+        let cutoff = match entry.bound {
+          Bound::Exact => true,
+          Bound::Lower => entry.score >= beta,
+          Bound::Upper => entry.score <= alpha,
+        };
+        if cutoff {
+          let (best, score) = (entry.best.clone(), entry.score);
+          if let Some(mv) = &best {
+            stack.update_pv(ply, mv.clone());
+          }
+          return Some((best, score));
+        }
+      }
+      if hint.is_none() {
+        hint = entry.best.clone();
+      }
+    }
+    let hint = hint.and_then(|mv| stack.board.find_move(team, &mv));
+
+    let (moves, phase) = if depth <= 1 {
+      (hint.clone().into_iter().chain(stack.board.successors(team)).collect(), Phase::Leaf(0))
+    } else {
+      (stack.board.successors(team).collect(), Phase::Ordering(0))
+    };
+    stack.frames.push(Frame {
+      team,
+      depth,
+      ply,
+      key,
+      first_alpha: alpha,
+      alpha,
+      beta,
+      hint,
+      leaf: depth <= 1,
+      moves,
+      beam: SmallVec::new(),
+      phase,
+      best: None,
+      score: LOSS,
+    });
+    None
+  }
+
+  /// Work on `stack` until its root is done, returning the root's
+  /// best move and score, or pause once `nodes` reaches `stop_at`.
+  fn run(&mut self, stack: &mut Stack, stop_at: Option<u64>) -> Option<(Option<CompactMove>, i64)> {
+    loop {
+      if let Some(stop_at) = stop_at {
+        if self.nodes >= stop_at {
+          return None;
+        }
+      }
+      if let Some(result) = self.advance(stack) {
+        return Some(result);
+      }
+    }
+  }
+
+  /// Do one step of the last node: score or search one move, or
+  /// hand its result on. Return the root's result once it is done.
+  fn advance(&mut self, stack: &mut Stack) -> Option<(Option<CompactMove>, i64)> {
+    let top = stack.frames.len() - 1;
+    match stack.frames[top].phase {
+      Phase::Leaf(i) => {
+        let frame = &stack.frames[top];
+        if i >= frame.moves.len() {
+          stack.frames[top].phase = Phase::Finished;
+          return None;
+        }
+        let (mv, team) = (frame.moves[i].clone(), frame.team);
+        stack.board.apply_move(&mv);
+        let eval = -self.evaluate(&stack.board, team.other());
+        stack.board.un_apply_move(&mv);
+
+        let frame = &mut stack.frames[top];
+        if self.aborted {
+          frame.phase = Phase::Finished;
+          return None;
+        }
+        if frame.best.is_none() || frame.score < eval {
+          frame.score = eval;
+          frame.best = Some(mv.clone());
+          let ply = frame.ply;
+          stack.update_pv(ply, mv);
+        }
+        let frame = &mut stack.frames[top];
+        frame.phase = if frame.score >= frame.beta { Phase::Finished } else { Phase::Leaf(i + 1) };
+      }
+
+      Phase::Ordering(i) => {
+        let frame = &stack.frames[top];
+        if i < frame.moves.len() {
+          let (mv, team) = (frame.moves[i].clone(), frame.team);
+          stack.board.apply_move(&mv);
+          let eval = self.evaluate(&stack.board, team);
+          stack.board.un_apply_move(&mv);
+          let frame = &mut stack.frames[top];
+          top_n_push(self.config.beam_width, &mut frame.beam, (eval, mv));
+          frame.phase = Phase::Ordering(i + 1);
+          return None;
+        }
+
+        let frame = &mut stack.frames[top];
+        if let Some(hint) = frame.hint.take() {
+          match frame.beam.iter().position(|(_, m)| *m == hint) {
+            Some(i) => {
+              let it = frame.beam.remove(i);
+              frame.beam.insert(0, it);
+            }
+            None => frame.beam.insert(0, (0, hint)),
+          }
+        }
+        frame.moves = frame.beam.drain(..).map(|(_, m)| m).collect();
+        frame.phase = Phase::Searching(0);
+      }
+
+      Phase::Searching(i) => {
+        let frame = &stack.frames[top];
+        if self.aborted || i >= frame.moves.len() {
+          stack.frames[top].phase = Phase::Finished;
+          return None;
+        }
+        let mv = frame.moves[i].clone();
+        let (team, depth, alpha, beta, ply) = (frame.team.other(), frame.depth - 1, frame.alpha, frame.beta, frame.ply + 1);
+        stack.board.apply_move(&mv);
+        if let Some((_, score)) = self.enter(stack, team, depth, -beta, -alpha, ply) {
+          stack.reply_searched(score);
+        }
+      }
+
+      Phase::Finished => {
+        let frame = &mut stack.frames[top];
+        if frame.best.is_none() && !frame.leaf {
+          // no reply was good enough to pick, so just take the
+          // best immediate move
+          frame.leaf = true;
+          frame.moves = stack.board.successors(frame.team).collect();
+          frame.phase = Phase::Leaf(0);
+          let ply = frame.ply;
+          stack.start_pv(ply);
+          return None;
+        }
+
+        let frame = stack.frames.pop().unwrap();
+        if !self.aborted {
+          if let Some(tt) = self.tt.as_mut() {
+            let bound = if frame.score <= frame.first_alpha {
+              Bound::Upper
+            } else if frame.score >= frame.beta {
+              Bound::Lower
+            } else {
+              Bound::Exact
+            };
+            tt.store(frame.key, frame.depth, bound, frame.score, frame.best.clone());
+          }
+        }
+        if stack.frames.is_empty() {
+          return Some((frame.best, frame.score));
+        }
+        stack.reply_searched(frame.score);
+      }
+    }
+    None
+  }
+}
+
+/// The `n` highest scored items, best first.
+pub fn top_n<A>(n: usize, iter: impl Iterator<Item = (i64, A)>) -> SmallVec<[(i64, A); 15]> {
+  let mut vec = SmallVec::<[(i64, A); 15]>::new();
+  iter.for_each(|new| top_n_push(n, &mut vec, new));
   return vec;
 }
+
+/// Add one item to a `top_n` result.
+pub fn top_n_push<A>(n: usize, vec: &mut SmallVec<[(i64, A); 15]>, new: (i64, A)) {
+  match vec.binary_search_by_key(&-new.0, |a| -a.0) {
+    Ok(i) => vec.insert(i, new),
+    Err(i) => vec.insert(i, new),
+  }
+  vec.truncate(n.max(1))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn start() -> CompactBoard {
+    CompactBoard::new(&Board::starting(10, 10, 2, 4).unwrap())
+  }
+
+  #[cfg(not(target_arch = "wasm32"))]
+  #[test]
+  fn parallel_node_budget() {
+    let config = EngineConfig { depth: 10, nodes: Some(20000), ..EngineConfig::default() };
+    let mut caches = vec![DistState::new(); 4];
+    let info = parallel_iterative_deepening(&mut caches, None, &start(), Team::Red, config);
+    // each thread may finish ordering the replies it is scoring
+    let replies = start().successors(Team::Red).count() as u64;
+    assert!(info.nodes <= 20000 + 4 * replies, "{} nodes", info.nodes);
+    assert!(info.depth >= 1);
+  }
+  #[test]
+  fn transpositions_keep_result() {
+    let config = EngineConfig { depth: 3, ..EngineConfig::default() };
+    let board = start();
+    let mut cache = DistState::new();
+    let plain = iterative_deepening(&mut cache, None, &board, Team::Red, config);
+    let mut tt = TranspositionTable::with_memory(1 << 20);
+    let with_tt = iterative_deepening(&mut cache, Some(&mut tt), &board, Team::Red, config);
+    assert_eq!(with_tt.best(), plain.best());
+    assert_eq!(with_tt.score, plain.score);
+
+    // again with the table already full from the last search
+    let again = iterative_deepening(&mut cache, Some(&mut tt), &board, Team::Red, config);
+    assert_eq!(again.best(), plain.best());
+    assert_eq!(again.score, plain.score);
+  }
+  #[test]
+  fn steps_search_the_same_tree() {
+    let config = EngineConfig { depth: 3, ..EngineConfig::default() };
+    let board = start();
+    let mut tt = TranspositionTable::with_memory(1 << 20);
+    let whole = iterative_deepening(&mut DistState::new(), Some(&mut tt), &board, Team::Red, config);
+
+    tt.clear();
+    let mut stepped = SteppedSearch::new(&board, Team::Red, config);
+    let mut steps = 0;
+    let progress = loop {
+      let progress = stepped.search_step(Some(&mut tt), 100);
+      steps += 1;
+      if progress.done {
+        break progress;
+      }
+      assert!(progress.info.nodes <= steps * 100);
+    };
+    assert!(steps > 10);
+    assert_eq!(progress.info.pv, whole.pv);
+    assert_eq!(progress.info.score, whole.score);
+    assert_eq!(progress.info.depth, whole.depth);
+    assert_eq!(progress.info.nodes, whole.nodes);
+  }
+}
@@ -1,18 +1,43 @@
-/// Red or Blue.
+use crate::compact_board::MAX_NUM_PLAYERS;
+use crate::rules::*;
+
+/// Red or Blue, joined by Green and Yellow in games
+/// of three or four teams.
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum Team {
   Red,
   Blue,
+  Green,
+  Yellow,
 }
+
+/// Most teams in one game.
+pub const NUM_TEAMS: usize = 4;
+
 impl Team {
+  /// Every team, in turn order.
   pub fn teams() -> Vec<Team> {
-    return vec![Team::Red, Team::Blue];
+    vec![Team::Red, Team::Blue, Team::Green, Team::Yellow]
+  }
+
+  /// Position in `teams`.
+  pub fn index(&self) -> usize {
+    match self {
+      Team::Red => 0,
+      Team::Blue => 1,
+      Team::Green => 2,
+      Team::Yellow => 3,
+    }
   }
 
+  /// The opponent in a two-team game: Red and Blue play each
+  /// other, as do Green and Yellow.
   pub fn other(&self) -> Team {
     match self {
       Team::Red => Team::Blue,
       Team::Blue => Team::Red,
+      Team::Green => Team::Yellow,
+      Team::Yellow => Team::Green,
     }
   }
 }
@@ -78,12 +103,66 @@ pub struct Player {
   pub pos: Pos,
 }
 
-/// 3 positions that represent how the piece moves
+/// Where the piece moves, and where its arrows land
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct Move {
   pub old_pos: Pos,
   pub new_pos: Pos,
-  pub new_shot: Pos,
+  /// In the order shot, as many as `Rules::arrows`.
+  pub shots: Shots,
+}
+
+/// Why a `Move` breaks the rules, from `Board::check_move`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MoveError {
+  /// A square is outside the playing area.
+  OffBoard(Pos),
+  /// There is no piece of the moving team at `old_pos`.
+  NotYourPiece,
+  /// `new_pos` is not an empty square the piece can reach.
+  BlockedMove,
+  /// A shot is not an empty square an arrow from `new_pos` can
+  /// reach, once the piece has moved and earlier arrows landed.
+  BlockedShot,
+  /// The move does not shoot the number of arrows the rules say.
+  ArrowCount(usize),
+}
+
+impl std::fmt::Display for MoveError {
+  fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+    match self {
+      MoveError::OffBoard(p) => write!(f, "({}, {}) is off the board", p.row, p.col),
+      MoveError::NotYourPiece => write!(f, "that is not one of your pieces"),
+      MoveError::BlockedMove => write!(f, "the piece cannot move there"),
+      MoveError::BlockedShot => write!(f, "the piece cannot shoot there"),
+      MoveError::ArrowCount(n) => write!(f, "each move shoots {} arrows", n),
+    }
+  }
+}
+
+/// Why a position cannot be played from, from `Board::check_position`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PositionError {
+  /// More pieces than the solver can track.
+  TooManyPieces,
+  /// Fewer than two teams have pieces.
+  TooFewTeams,
+  /// The team to move has no pieces.
+  NoPieces(Team),
+  /// The team to move is out of the game.
+  OutToMove(Team),
+}
+
+impl std::fmt::Display for PositionError {
+  fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+    match self {
+      PositionError::TooManyPieces =>
+        write!(f, "there can be at most {} pieces", MAX_NUM_PLAYERS),
+      PositionError::TooFewTeams => write!(f, "at least two teams need pieces"),
+      PositionError::NoPieces(t) => write!(f, "{:?} has no pieces", t),
+      PositionError::OutToMove(t) => write!(f, "{:?} is out of the game", t),
+    }
+  }
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -96,27 +175,97 @@ pub enum BoardSlot {
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Board {
   pub board: Vec<Vec<BoardSlot>>,
+  /// How this game is played.
+  pub rules: Rules,
 }
 
+/// Playable rows and columns for `Board::starting`.
+pub const MIN_BOARD_SIZE: usize = 6;
+pub const MAX_BOARD_SIZE: usize = 16;
+
 impl Board {
+  /// The 8x8 game with two amazons each.
   pub fn new() -> Self {
-    let board_size = 8 + 2; // 8x8 with walls
-    let mut tokens = vec![vec![BoardSlot::Empty; board_size]; board_size];
+    Board::starting(8, 8, 2, 2).unwrap()
+  }
+
+  /// Starting position on a board of `rows` by `cols` for 2 to
+  /// `NUM_TEAMS` teams with `amazons` each, Red near row 1.
+  ///
+  /// Two teams may have 2 or 4 amazons; with 4 on 10x10 this is
+  /// the standard game. More teams have 2 each, Blue near the last
+  /// row, Green near column a and Yellow near the last column. None for other
+  /// setups or sides outside `MIN_BOARD_SIZE` to `MAX_BOARD_SIZE`.
+  pub fn starting(rows: usize, cols: usize, teams: usize, amazons: usize) -> Option<Board> {
+    let mut board = Board::empty(rows, cols)?;
+    let tokens = &mut board.board;
+
+    // `k` in from the corners: 3 on 8x8, 4 on 10x10
+    let (kr, kc) = ((rows - 2) / 2, (cols - 2) / 2);
+    let far = cols + 1 - kc;
+    let red = match (teams, amazons) {
+      (2, 2) => vec![(kr, kc), (kr, far)],
+      (2, 4) => vec![(1, kc), (1, far), (kr, 1), (kr, cols)],
+      (3..=NUM_TEAMS, 2) => vec![(1, kc), (1, far)],
+      _ => return None,
+    };
+    for (r, c) in red {
+      tokens[r][c] = BoardSlot::Piece(Team::Red);
+      tokens[rows + 1 - r][c] = BoardSlot::Piece(Team::Blue);
+    }
+    for (team, c) in Team::teams().into_iter().skip(2).take(teams - 2).zip(vec![1, cols]) {
+      tokens[kr][c] = BoardSlot::Piece(team);
+      tokens[rows + 1 - kr][c] = BoardSlot::Piece(team);
+    }
 
-    for r in 0..board_size {
-      for c in 0..board_size {
-        if r == 0 || c == 0 || r == board_size - 1 || c == board_size - 1 {
+    Some(board)
+  }
+
+  /// A board of `rows` by `cols` with nothing on it, or None for
+  /// sides outside `MIN_BOARD_SIZE` to `MAX_BOARD_SIZE`.
+  pub fn empty(rows: usize, cols: usize) -> Option<Board> {
+    let sizes = MIN_BOARD_SIZE..=MAX_BOARD_SIZE;
+    if !sizes.contains(&rows) || !sizes.contains(&cols) {
+      return None;
+    }
+    // with walls
+    let (rows, cols) = (rows + 2, cols + 2);
+    let mut tokens = vec![vec![BoardSlot::Empty; cols]; rows];
+
+    for r in 0..rows {
+      for c in 0..cols {
+        if r == 0 || c == 0 || r == rows - 1 || c == cols - 1 {
           tokens[r][c] = BoardSlot::Wall;
         }
       }
     }
+    Some(Board { board: tokens, rules: Rules::default() })
+  }
 
-    tokens[3][3] = BoardSlot::Piece(Team::Red);
-    tokens[3][6] = BoardSlot::Piece(Team::Red);
-    tokens[6][3] = BoardSlot::Piece(Team::Blue);
-    tokens[6][6] = BoardSlot::Piece(Team::Blue);
+  /// Whether a game can start from this position: at least two
+  /// teams have a piece, and there are at most `MAX_NUM_PLAYERS`.
+  pub fn check_position(&self) -> Result<(), PositionError> {
+    let pieces = self.board.iter().flatten()
+      .filter(|s| matches!(s, BoardSlot::Piece(_)))
+      .count();
+    if pieces > MAX_NUM_PLAYERS {
+      return Err(PositionError::TooManyPieces);
+    }
+    if self.teams().len() < 2 {
+      return Err(PositionError::TooFewTeams);
+    }
+    Ok(())
+  }
 
-    return Board { board: tokens };
+  /// Teams with pieces on the board, in turn order.
+  pub fn teams(&self) -> Vec<Team> {
+    let mut found = [false; NUM_TEAMS];
+    for slot in self.board.iter().flatten() {
+      if let BoardSlot::Piece(t) = slot {
+        found[t.index()] = true;
+      }
+    }
+    Team::teams().into_iter().filter(|t| found[t.index()]).collect()
   }
 
   pub fn pprint(&self) -> String {
@@ -128,6 +277,8 @@ impl Board {
           BoardSlot::Wall => '#',
           BoardSlot::Piece(Team::Red) => 'R',
           BoardSlot::Piece(Team::Blue) => 'B',
+          BoardSlot::Piece(Team::Green) => 'G',
+          BoardSlot::Piece(Team::Yellow) => 'Y',
         });
       }
       s.push('\n');
@@ -135,8 +286,79 @@ impl Board {
     return s;
   }
 
-  pub fn size(&self) -> usize {
-    self.board.len().max(self.board.iter().map(|r| r.len()).max().unwrap())
+  /// Dimensions, then every slot row by row (walls included)
+  /// as 0 empty, 1 wall, 2 red, 3 blue, 4 green, 5 yellow,
+  /// then `Rules::to_bytes`.
+  pub fn to_bytes(&self) -> Vec<u8> {
+    let mut v = vec![self.rows() as u8, self.cols() as u8];
+    for row in &self.board {
+      for p in row {
+        v.push(match p {
+          BoardSlot::Empty => 0,
+          BoardSlot::Wall => 1,
+          BoardSlot::Piece(Team::Red) => 2,
+          BoardSlot::Piece(Team::Blue) => 3,
+          BoardSlot::Piece(Team::Green) => 4,
+          BoardSlot::Piece(Team::Yellow) => 5,
+        });
+      }
+    }
+    v.extend(&self.rules.to_bytes());
+    v
+  }
+
+  /// Inverse of `to_bytes`. None unless the board is a size
+  /// `empty` allows, surrounded by walls. Without rules, they are
+  /// the default.
+  pub fn from_bytes(bytes: &[u8]) -> Option<Board> {
+    if bytes.len() < 2 {
+      return None;
+    }
+    let (rows, cols) = (bytes[0] as usize, bytes[1] as usize);
+    // sides include the walls
+    let sizes = MIN_BOARD_SIZE + 2..=MAX_BOARD_SIZE + 2;
+    if !sizes.contains(&rows) || !sizes.contains(&cols) {
+      return None;
+    }
+    let slots = 2 + rows * cols;
+    let rules = match &bytes[slots.min(bytes.len())..] {
+      [] => Rules::default(),
+      &[a, b, c] => Rules::from_bytes([a, b, c])?,
+      _ => return None,
+    };
+    if bytes.len() < slots {
+      return None;
+    }
+
+    let mut tokens = vec![vec![BoardSlot::Empty; cols]; rows];
+    for r in 0..rows {
+      for c in 0..cols {
+        tokens[r][c] = match bytes[2 + r * cols + c] {
+          0 => BoardSlot::Empty,
+          1 => BoardSlot::Wall,
+          2 => BoardSlot::Piece(Team::Red),
+          3 => BoardSlot::Piece(Team::Blue),
+          4 => BoardSlot::Piece(Team::Green),
+          5 => BoardSlot::Piece(Team::Yellow),
+          _ => return None,
+        };
+        let border = r == 0 || c == 0 || r == rows - 1 || c == cols - 1;
+        if border && tokens[r][c] != BoardSlot::Wall {
+          return None;
+        }
+      }
+    }
+    Some(Board { board: tokens, rules })
+  }
+
+  /// Rows, counting the walls around the board.
+  pub fn rows(&self) -> usize {
+    self.board.len()
+  }
+
+  /// Columns, counting the walls around the board.
+  pub fn cols(&self) -> usize {
+    self.board[0].len()
   }
 
   pub fn open_line_along(&mut self, start: Pos, end: Pos) -> bool {
@@ -158,13 +380,135 @@ impl Board {
     *self.at(b) = t;
   }
 
+  /// Whether `team` may play `mv` under `self.rules`: its piece
+  /// moves along a line to an empty square, then shoots its arrows
+  /// one at a time from there to others, possibly through or onto
+  /// the square it left.
+  pub fn check_move(&mut self, team: Team, mv: Move) -> Result<(), MoveError> {
+    let arrows = self.rules.arrows;
+    if mv.shots.iter().take(arrows).any(|s| s.is_none()) || mv.shots[arrows..].iter().any(|s| s.is_some()) {
+      return Err(MoveError::ArrowCount(arrows));
+    }
+    let (rows, cols) = (self.rows() as i8, self.cols() as i8);
+    for &p in [mv.old_pos, mv.new_pos].iter().chain(mv.shots.iter().flatten()) {
+      // the outermost ring is walls
+      if p.row < 1 || p.col < 1 || p.row >= rows - 1 || p.col >= cols - 1 {
+        return Err(MoveError::OffBoard(p));
+      }
+    }
+    if *self.at(mv.old_pos) != BoardSlot::Piece(team) {
+      return Err(MoveError::NotYourPiece);
+    }
+    let rules = self.rules;
+    if !rules.can_move(self, mv.old_pos, mv.new_pos) {
+      return Err(MoveError::BlockedMove);
+    }
+    self.swap_pos(mv.old_pos, mv.new_pos);
+    let mut landed = 0;
+    for &shot in mv.shots.iter().flatten() {
+      if !rules.can_shoot(self, mv.new_pos, shot) {
+        break;
+      }
+      *self.at(shot) = BoardSlot::Wall;
+      landed += 1;
+    }
+    for &shot in mv.shots.iter().flatten().take(landed) {
+      *self.at(shot) = BoardSlot::Empty;
+    }
+    self.swap_pos(mv.old_pos, mv.new_pos);
+    if landed < arrows {
+      return Err(MoveError::BlockedShot);
+    }
+    Ok(())
+  }
+
   pub fn apply_move(&mut self, mv: Move) {
     self.swap_pos(mv.old_pos, mv.new_pos);
-    *self.at(mv.new_shot) = BoardSlot::Wall;
+    for &shot in mv.shots.iter().flatten() {
+      *self.at(shot) = BoardSlot::Wall;
+    }
   }
 
   pub fn un_apply_move(&mut self, mv: Move) {
-    *self.at(mv.new_shot) = BoardSlot::Empty;
+    for &shot in mv.shots.iter().flatten() {
+      *self.at(shot) = BoardSlot::Empty;
+    }
     self.swap_pos(mv.old_pos, mv.new_pos);
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// The 8x8 start: Red on c3 and f3, Blue on c6 and f6.
+  fn check(text: &str) -> Result<(), MoveError> {
+    let mut board = Board::new();
+    let before = board.clone();
+    let result = board.check_move(Team::Red, text.parse().unwrap());
+    assert_eq!(board, before, "{} changed the board", text);
+    result
+  }
+
+  #[test]
+  fn legal_moves() {
+    assert_eq!(check("c3-c5/e5"), Ok(()));
+    assert_eq!(check("c3-a1/a8"), Ok(()));
+    // through and onto the square just left
+    assert_eq!(check("c3-c4/c1"), Ok(()));
+    assert_eq!(check("c3-c4/c3"), Ok(()));
+  }
+
+  #[test]
+  fn wrong_team() {
+    assert_eq!(check("c6-c5/c4"), Err(MoveError::NotYourPiece));
+    assert_eq!(check("d3-d4/d5"), Err(MoveError::NotYourPiece));
+  }
+
+  #[test]
+  fn blocked() {
+    assert_eq!(check("c3-c7/c8"), Err(MoveError::BlockedMove));
+    assert_eq!(check("c3-c6/c5"), Err(MoveError::BlockedMove));
+    assert_eq!(check("c3-d5/d6"), Err(MoveError::BlockedMove));
+    assert_eq!(check("c3-c3/c4"), Err(MoveError::BlockedMove));
+    assert_eq!(check("c3-c5/c7"), Err(MoveError::BlockedShot));
+    assert_eq!(check("c3-c5/c6"), Err(MoveError::BlockedShot));
+    assert_eq!(check("c3-c4/c4"), Err(MoveError::BlockedShot));
+  }
+
+  #[test]
+  fn arrow_counts() {
+    assert_eq!(check("c3-c4"), Err(MoveError::ArrowCount(1)));
+    assert_eq!(check("c3-c4/c1/c2"), Err(MoveError::ArrowCount(1)));
+
+    let mut board = Board::new();
+    board.rules = Rules { arrows: 2, ..Rules::default() };
+    assert_eq!(board.check_move(Team::Red, "c3-c4/c1".parse().unwrap()), Err(MoveError::ArrowCount(2)));
+    assert_eq!(board.check_move(Team::Red, "c3-c4/c1/c2".parse().unwrap()), Ok(()));
+    // the first arrow blocks the second
+    assert_eq!(board.check_move(Team::Red, "c3-c4/c2/c1".parse().unwrap()), Err(MoveError::BlockedShot));
+  }
+
+  #[test]
+  fn off_board() {
+    let mv = |old_pos, new_pos, shot| Move { old_pos, new_pos, shots: [Some(shot), None] };
+    let (c3, c5, e5) = (Pos { row: 3, col: 3 }, Pos { row: 5, col: 3 }, Pos { row: 5, col: 5 });
+    for &outside in [Pos { row: 0, col: 3 }, Pos { row: 9, col: 3 }, Pos { row: 3, col: 0 }, Pos { row: -1, col: 9 }].iter() {
+      let mut board = Board::new();
+      assert_eq!(board.check_move(Team::Red, mv(outside, c5, e5)), Err(MoveError::OffBoard(outside)));
+      assert_eq!(board.check_move(Team::Red, mv(c3, outside, e5)), Err(MoveError::OffBoard(outside)));
+      assert_eq!(board.check_move(Team::Red, mv(c3, c5, outside)), Err(MoveError::OffBoard(outside)));
+    }
+  }
+
+  #[test]
+  fn bytes_round_trip() {
+    let mut board = Board::starting(6, 9, 3, 2).unwrap();
+    board.rules = Rules { move_range: Some(2), arrow_range: None, arrows: 2 };
+    assert_eq!(Board::from_bytes(&board.to_bytes()), Some(board));
+
+    let mut big = vec![(MAX_BOARD_SIZE + 3) as u8, 10];
+    big.extend(vec![1; (MAX_BOARD_SIZE + 3) * 10]);
+    assert_eq!(Board::from_bytes(&big), None);
+  }
+}
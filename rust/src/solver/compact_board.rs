@@ -1,22 +1,23 @@
 use crate::board::*;
+use crate::rules::*;
 
 use bv::BitVec;
 use std::collections::VecDeque;
 
 /// Player and what they do.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct CompactMove {
   pub player_ix: usize,
   pub old_pos: Pos,
   pub new_pos: Pos,
-  pub new_shot: Pos,
+  pub shots: Shots,
 }
 impl From<CompactMove> for Move {
   fn from(cm: CompactMove) -> Move {
     Move {
       old_pos: cm.old_pos,
       new_pos: cm.new_pos,
-      new_shot: cm.new_shot,
+      shots: cm.shots,
     }
   }
 }
@@ -25,6 +26,8 @@ impl From<CompactMove> for Move {
 pub struct DistState {
   left: Vec<u8>,
   right: Vec<u8>,
+  /// Distances for each team, for `evaluate_territory_multi`.
+  teams: Vec<Vec<u8>>,
   next: VecDeque<(Pos, u8)>,
 }
 impl DistState {
@@ -32,36 +35,45 @@ impl DistState {
     DistState {
       left: Vec::new(),
       right: Vec::new(),
+      teams: Vec::new(),
       next: VecDeque::new(),
     }
   }
 }
 
-const MAX_NUM_PLAYERS: usize = 4;
+/// Pieces of both teams together, enough for the standard
+/// four amazons each.
+pub const MAX_NUM_PLAYERS: usize = 8;
 
 /// Game state at an instant.
 #[derive(Clone, Debug)]
 pub struct CompactBoard {
-  pub board_size: i8,
+  /// Rows and columns, counting the walls around the board.
+  pub rows: i8,
+  pub cols: i8,
+  pub rules: Rules,
   walls: BitVec,
   players_array: [Player; MAX_NUM_PLAYERS],
+  hash: u64,
 }
 
 impl CompactBoard {
   pub fn new(board: &Board) -> CompactBoard {
-    let board_size = board.size();
+    let (rows, cols) = (board.rows(), board.cols());
 
-    let mut b = BitVec::new_fill(false, (board_size * board_size) as u64);
+    let mut b = BitVec::new_fill(false, (rows * cols) as u64);
 
     let mut players = [Player { pos: Pos {row:0, col:0}, team: Team::Red }; MAX_NUM_PLAYERS];
     let mut player_ix = 0;
+    let mut hash = zobrist_rules(board.rules);
 
-    for r in 0..board_size {
-      for c in 0..board_size {
+    for r in 0..rows {
+      for c in 0..cols {
         match board.board[r][c] {
           BoardSlot::Empty => {}
           BoardSlot::Wall => {
-            b.set((r * board_size + c) as u64, true);
+            b.set((r * cols + c) as u64, true);
+            hash ^= zobrist(Pos { row: r as i8, col: c as i8 }, None);
           }
           BoardSlot::Piece(team) => {
             assert!(player_ix < MAX_NUM_PLAYERS);
@@ -71,7 +83,8 @@ impl CompactBoard {
             players[player_ix].pos.col = c as i8;
             players[player_ix].team = team;
             player_ix += 1;
-            b.set((r * board_size + c) as u64, true);
+            b.set((r * cols + c) as u64, true);
+            hash ^= zobrist(Pos { row: r as i8, col: c as i8 }, Some(team));
           }
         }
       }
@@ -79,37 +92,45 @@ impl CompactBoard {
 
      return CompactBoard {
       walls: b,
-      board_size: board_size as i8,
+      rows: rows as i8,
+      cols: cols as i8,
+      rules: board.rules,
       players_array: players,
+      hash,
     };
   }
 
+  /// Zobrist hash of the arrows, pieces and rules, kept up to
+  /// date by `apply_move` and `un_apply_move`.
+  pub fn hash(&self) -> u64 {
+    self.hash
+  }
+
   pub fn wall_set(&mut self, p: Pos, val: bool) {
-    self.walls.set(p.to_linear(self.board_size) as u64, val);
+    self.walls.set(p.to_linear(self.cols) as u64, val);
   }
   pub fn wall_at(&self, p: Pos) -> bool {
-    self.walls.get((p.to_linear(self.board_size)) as u64)
+    self.walls.get((p.to_linear(self.cols)) as u64)
   }
 
   pub fn pprint(&self) -> String {
     let mut s = String::new();
-    for r in 0..self.board_size {
-      for c in 0..self.board_size {
+    for r in 0..self.rows {
+      for c in 0..self.cols {
         let pos = Pos { row: r, col: c };
         match self.players().find(|p| p.pos == pos) {
           Some(p) => {
-            if !self.wall_at(pos) {
-              if p.team == Team::Blue {
-                s.push('b');
-              } else {
-                s.push('w');
-              }
+            // as `Board::pprint`, lowercase if the square is not blocked
+            let c = match p.team {
+              Team::Red => 'R',
+              Team::Blue => 'B',
+              Team::Green => 'G',
+              Team::Yellow => 'Y',
+            };
+            if self.wall_at(pos) {
+              s.push(c);
             } else {
-              if p.team == Team::Blue {
-                s.push('B');
-              } else {
-                s.push('W');
-              }
+              s.push(c.to_ascii_lowercase());
             }
           }
           None => {
@@ -138,32 +159,48 @@ impl CompactBoard {
     // invalid players in the array, thus the indexes match
     self.wall_set(mv.old_pos, false);
     self.wall_set(mv.new_pos, true);
-    self.wall_set(mv.new_shot, true);
+    for &shot in mv.shots.iter().flatten() {
+      self.wall_set(shot, true);
+    }
     self.players_array[mv.player_ix].pos = mv.new_pos;
+    self.hash ^= move_hash(mv, self.players_array[mv.player_ix].team);
   }
 
   pub fn un_apply_move(&mut self, mv: &CompactMove) {
     // NOTE: this only works since valid players are before the
     // invalid players in the array, thus the indexes match
     self.wall_set(mv.new_pos, false);
-    self.wall_set(mv.new_shot, false);
+    for &shot in mv.shots.iter().flatten() {
+      self.wall_set(shot, false);
+    }
     self.wall_set(mv.old_pos, true);
     self.players_array[mv.player_ix].pos = mv.old_pos;
+    self.hash ^= move_hash(mv, self.players_array[mv.player_ix].team);
+  }
+
+  /// The legal move for `team` going through the same squares as
+  /// `mv`, with `player_ix` corrected for this board.
+  pub fn find_move(&self, team: Team, mv: &CompactMove) -> Option<CompactMove> {
+    self.successors(team).find(|m| {
+      m.old_pos == mv.old_pos && m.new_pos == mv.new_pos && m.shots == mv.shots
+    })
   }
 
+  /// Legal moves for `team` under `self.rules`.
   pub fn successors<'a>(&'a self, team: Team) -> impl Iterator<Item = CompactMove> + 'a {
+    let rules = self.rules;
     self
       .players()
       .enumerate()
       .filter(move |(_, player)| player.team == team)
       .flat_map(move |(pi, player): (usize, &'a Player)| {
-        queen_range(self, player.pos, player.pos).flat_map(move |pos: Pos| {
-          queen_range(self, pos, player.pos).map(move |shot: Pos| {
+        rules.piece_moves(self, player.pos).flat_map(move |pos: Pos| {
+          rules.shot_sets(self, pos, player.pos).map(move |shots: Shots| {
             CompactMove {
               player_ix: pi,
               old_pos: player.pos,
               new_pos: pos,
-              new_shot: shot,
+              shots,
             }
           })
         })
@@ -171,6 +208,48 @@ impl CompactBoard {
   }
 }
 
+/// Zobrist key for an arrow (`None`) or a team's piece at `pos`.
+///
+/// Keys are derived from the coordinates instead of a table
+/// so they work for any board size.
+fn zobrist(pos: Pos, piece: Option<Team>) -> u64 {
+  let kind = match piece {
+    None => 0,
+    Some(team) => team.index() as u64 + 1,
+  };
+  splitmix64(((pos.row as u8 as u64) << 16) | ((pos.col as u8 as u64) << 8) | kind)
+}
+
+/// Key for the rules a position is played by, so that the same
+/// squares under other rules hash differently.
+fn zobrist_rules(rules: Rules) -> u64 {
+  let [move_range, arrow_range, arrows] = rules.to_bytes();
+  splitmix64(1 << 32 | (move_range as u64) << 16 | (arrow_range as u64) << 8 | arrows as u64)
+}
+
+/// Key to mix into a position hash for the side to move.
+pub fn zobrist_turn(team: Team) -> u64 {
+  match team {
+    Team::Red => 0,
+    _ => splitmix64(u64::MAX - (team.index() as u64 - 1)),
+  }
+}
+
+/// Change to a board hash when `team` plays `mv`. Self-inverse.
+fn move_hash(mv: &CompactMove, team: Team) -> u64 {
+  mv.shots.iter().flatten().fold(
+    zobrist(mv.old_pos, Some(team)) ^ zobrist(mv.new_pos, Some(team)),
+    |hash, &shot| hash ^ zobrist(shot, None),
+  )
+}
+
+fn splitmix64(seed: u64) -> u64 {
+  let mut z = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+  z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+  z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+  z ^ (z >> 31)
+}
+
 pub fn evaluate_by_queen_bfs_distance(board: &CompactBoard, team: Team, dist_state: &mut DistState) -> i64 {
   bfs(board, team, &mut dist_state.next, &mut dist_state.left);
   bfs(board, team.other(), &mut dist_state.next, &mut dist_state.right);
@@ -197,11 +276,44 @@ pub fn evaluate_by_queen_bfs_distance(board: &CompactBoard, team: Team, dist_sta
   return score;
 }
 
+/// Territory in games of any number of teams: the squares `team`
+/// reaches in fewer queen moves than each other team in `teams`,
+/// less those of whichever of them has most.
+pub fn evaluate_territory_multi(board: &CompactBoard, team: Team, teams: &[Team], dist_state: &mut DistState) -> i64 {
+  dist_state.teams.resize(teams.len(), Vec::new());
+  for (t, distances) in teams.iter().zip(dist_state.teams.iter_mut()) {
+    bfs(board, *t, &mut dist_state.next, distances);
+  }
+
+  let mut owned = [0i64; NUM_TEAMS];
+  for sq in 0..board.rows as usize * board.cols as usize {
+    let mut closest: Option<Team> = None;
+    let mut best = u8::max_value();
+    for (t, distances) in teams.iter().zip(dist_state.teams.iter()) {
+      if distances[sq] < best {
+        best = distances[sq];
+        closest = Some(*t);
+      } else if distances[sq] == best {
+        closest = None;
+      }
+    }
+    if let Some(t) = closest {
+      owned[t.index()] += 1;
+    }
+  }
+  let rival = teams.iter()
+    .filter(|t| **t != team)
+    .map(|t| owned[t.index()])
+    .max()
+    .unwrap_or(0);
+  owned[team.index()] - rival
+}
+
 fn bfs(board: &CompactBoard, team: Team, next: &mut VecDeque<(Pos, u8)>, distances: &mut Vec<u8>) {
   for i in 0..distances.len() {
     distances[i] = u8::max_value();
   }
-  while distances.len() < (board.board_size * board.board_size) as usize {
+  while distances.len() < board.rows as usize * board.cols as usize {
     distances.push(u8::max_value());
   }
   next.clear();
@@ -212,8 +324,8 @@ fn bfs(board: &CompactBoard, team: Team, next: &mut VecDeque<(Pos, u8)>, distanc
     .for_each(|it| next.push_back(it));
 
   while let Some((pos, depth)) = next.pop_front() {
-    for neigh in queen_range(board, pos, pos) {
-      let place = &mut distances[neigh.to_linear(board.board_size)];
+    for neigh in board.rules.piece_moves(board, pos) {
+      let place = &mut distances[neigh.to_linear(board.cols)];
       if depth + 1 < *place {
         *place = depth + 1;
         next.push_back((neigh, depth + 1));
@@ -222,15 +334,42 @@ fn bfs(board: &CompactBoard, team: Team, next: &mut VecDeque<(Pos, u8)>, distanc
   }
 }
 
-const QUEEN_DIRS: [(i8,i8); 8] = [(-1,-1),(-1,0),(-1,1),
-                                  ( 0,-1)       ,( 0,1),
-                                  ( 1,-1),( 1,0),( 1,1)];
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::rng::Rng;
 
+  #[test]
+  fn hash_follows_moves() {
+    let mut board = Board::starting(8, 8, 2, 4).unwrap();
+    let mut compact = CompactBoard::new(&board);
+    let start = compact.hash();
+    let mut rng = Rng::new(7);
+    let mut played = vec![];
+    let mut team = Team::Red;
+    for _ in 0..20 {
+      let moves: Vec<CompactMove> = compact.successors(team).collect();
+      if moves.is_empty() {
+        break;
+      }
+      let mv = moves[rng.below(moves.len())].clone();
+      compact.apply_move(&mv);
+      board.apply_move(mv.clone().into());
+      assert_eq!(compact.hash(), CompactBoard::new(&board).hash());
+      played.push(mv);
+      team = team.other();
+    }
+    for mv in played.iter().rev() {
+      compact.un_apply_move(mv);
+    }
+    assert_eq!(compact.hash(), start);
+  }
 
-fn queen_range<'a>(board: &'a CompactBoard, from: Pos, blank: Pos) -> impl Iterator<Item = Pos> + 'a {
-  QUEEN_DIRS.iter().flat_map(move |dir| {
-    (1..)
-      .map(move |dist| from.with_offset(*dir, dist))
-      .take_while(move |place| !board.wall_at(*place) || *place == blank)
-  })
+  #[test]
+  fn hash_includes_rules() {
+    let mut board = Board::starting(8, 8, 2, 4).unwrap();
+    let hash = CompactBoard::new(&board).hash();
+    board.rules.arrows = 2;
+    assert_ne!(CompactBoard::new(&board).hash(), hash);
+  }
 }
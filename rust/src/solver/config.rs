@@ -0,0 +1,309 @@
+use crate::algo::{LOSS, WIN};
+use crate::board::*;
+use crate::compact_board::*;
+use crate::endgame::evaluate_with_endgame;
+use crate::mcts::{MctsParams, Playout};
+use crate::rng::Rng;
+
+/// Which search `ai_move` uses.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Engine {
+  /// Iterative deepening alpha-beta.
+  MinMax,
+  Mcts(MctsParams),
+}
+
+/// How positions are scored.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Evaluator {
+  /// Squares each team reaches first, `evaluate_by_queen_bfs_distance`.
+  Territory,
+  /// `Territory` until the teams are separated, then exact move
+  /// counts; separated endgames are also played out directly.
+  Endgame,
+}
+impl Evaluator {
+  pub fn evaluate(self, board: &CompactBoard, team: Team, cache: &mut DistState) -> i64 {
+    match self {
+      Evaluator::Territory => evaluate_by_queen_bfs_distance(board, team, cache),
+      Evaluator::Endgame => evaluate_with_endgame(board, team, cache),
+    }
+  }
+}
+
+/// Everything that tunes the AI. The default is the
+/// depth 3, beam 14 alpha-beta the game has always used.
+///
+/// Budgets are checked by iterative deepening, which always
+/// completes depth 1 so there is a move to return.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct EngineConfig {
+  pub engine: Engine,
+  /// Deepest alpha-beta iteration.
+  pub depth: i32,
+  /// Replies searched at each alpha-beta node, best first.
+  pub beam_width: usize,
+  /// Wall-clock budget in milliseconds.
+  pub time_ms: Option<f64>,
+  /// Number of positions evaluated.
+  pub nodes: Option<u64>,
+  pub evaluator: Evaluator,
+  /// Seed for anything random in the engine.
+  pub seed: u64,
+  /// Largest amount added to or taken from each evaluation, to
+  /// make the AI misjudge positions. 0 for none.
+  pub noise: i64,
+  /// Chance from 0 to 1 that `ai_move` plays a random move from
+  /// the beam instead of searching.
+  pub blunder: f64,
+}
+
+/// Preset strengths, from making obvious mistakes to
+/// searching deeper than the original AI.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Difficulty {
+  Beginner,
+  Easy,
+  Medium,
+  /// The original depth 3 search, `EngineConfig::default`.
+  Hard,
+  Expert,
+}
+
+impl Difficulty {
+  pub fn config(self) -> EngineConfig {
+    let default = EngineConfig::default();
+    match self {
+      Difficulty::Beginner => EngineConfig {
+        depth: 1,
+        beam_width: 6,
+        evaluator: Evaluator::Territory,
+        noise: 8,
+        blunder: 0.25,
+        ..default
+      },
+      Difficulty::Easy => EngineConfig {
+        depth: 1,
+        evaluator: Evaluator::Territory,
+        noise: 4,
+        blunder: 0.1,
+        ..default
+      },
+      Difficulty::Medium => EngineConfig {
+        depth: 2,
+        beam_width: 8,
+        noise: 1,
+        ..default
+      },
+      Difficulty::Hard => default,
+      Difficulty::Expert => EngineConfig {
+        depth: 6,
+        beam_width: 16,
+        time_ms: Some(3000.0),
+        ..default
+      },
+    }
+  }
+}
+
+impl Default for EngineConfig {
+  fn default() -> EngineConfig {
+    EngineConfig {
+      engine: Engine::MinMax,
+      depth: 3,
+      beam_width: 14,
+      time_ms: None,
+      nodes: None,
+      evaluator: Evaluator::Endgame,
+      seed: 0,
+      noise: 0,
+      blunder: 0.0,
+    }
+  }
+}
+
+impl EngineConfig {
+  /// Score `board` for `team` with the evaluator and noise.
+  ///
+  /// The noise depends only on the position and seed, so a position
+  /// scores the same wherever it is reached, and is opposite for the
+  /// two teams like the evaluation itself.
+  pub fn evaluate(&self, board: &CompactBoard, team: Team, cache: &mut DistState) -> i64 {
+    let eval = self.evaluator.evaluate(board, team, cache);
+    if self.noise <= 0 || eval == WIN || eval == LOSS {
+      return eval;
+    }
+    let mut rng = Rng::new(board.hash() ^ self.seed);
+    let noise = rng.below(2 * self.noise as usize + 1) as i64 - self.noise;
+    // each team against `other`, so Red and Green add it
+    match team {
+      Team::Red | Team::Green => eval + noise,
+      Team::Blue | Team::Yellow => eval - noise,
+    }
+  }
+
+  /// Key to mix into transposition table keys, so that scores from
+  /// another evaluator, or other noise, are not reused.
+  pub fn evaluation_key(&self) -> u64 {
+    let evaluator = match self.evaluator {
+      Evaluator::Territory => 0,
+      Evaluator::Endgame => 1,
+    };
+    // without noise the seed changes nothing, as in `evaluate`
+    let (noise, seed) = if self.noise > 0 { (self.noise as u64, self.seed) } else { (0, 0) };
+    // shifted, as `Rng::new` ignores the lowest bit
+    Rng::new(seed).next_u64() ^ Rng::new(noise << 2 | evaluator << 1).next_u64()
+  }
+
+  /// Set an option by name from text, as JS gives it to
+  /// `State.set_engine_option` and `Searcher.think`.
+  ///
+  /// Names are `depth`, `beam`, `time-ms`, `nodes`, `evaluator`
+  /// (`territory` or `endgame`), `engine` (`minmax`, `mcts` or
+  /// `mcts-random`), `iterations`, `playout` (`random` or plies
+  /// before evaluating), `exploration`, `seed`, `noise`, `blunder`
+  /// and `difficulty`. Budgets accept `none`.
+  pub fn set_option(&mut self, name: &str, value: &str) -> Result<(), String> {
+    fn num<T: std::str::FromStr>(name: &str, value: &str) -> Result<T, String> {
+      value.parse().map_err(|_| format!("bad value '{}' for {}", value, name))
+    }
+    fn budget<T: std::str::FromStr>(name: &str, value: &str) -> Result<Option<T>, String> {
+      if value == "none" { Ok(None) } else { num(name, value).map(Some) }
+    }
+
+    match name {
+      "depth" => self.depth = num(name, value)?,
+      "beam" => self.beam_width = num(name, value)?,
+      "time-ms" => self.time_ms = budget(name, value)?,
+      "nodes" => self.nodes = budget(name, value)?,
+      "seed" => self.seed = num(name, value)?,
+      "noise" => self.noise = num(name, value)?,
+      "blunder" => self.blunder = num(name, value)?,
+      "difficulty" => {
+        let level = match value {
+          "beginner" => Difficulty::Beginner,
+          "easy" => Difficulty::Easy,
+          "medium" => Difficulty::Medium,
+          "hard" => Difficulty::Hard,
+          "expert" => Difficulty::Expert,
+          _ => return Err(format!("unknown difficulty '{}'", value)),
+        };
+        *self = EngineConfig { seed: self.seed, ..level.config() };
+      }
+      "evaluator" => {
+        self.evaluator = match value {
+          "territory" => Evaluator::Territory,
+          "endgame" => Evaluator::Endgame,
+          _ => return Err(format!("unknown evaluator '{}'", value)),
+        }
+      }
+      "engine" => {
+        self.engine = match value {
+          "minmax" => Engine::MinMax,
+          "mcts" => Engine::Mcts(MctsParams::default()),
+          "mcts-random" => Engine::Mcts(MctsParams {
+            playout: Playout::Random,
+            ..MctsParams::default()
+          }),
+          _ => return Err(format!("unknown engine '{}'", value)),
+        }
+      }
+      "iterations" | "playout" | "exploration" => {
+        let params = match &mut self.engine {
+          Engine::Mcts(params) => params,
+          Engine::MinMax => return Err(format!("{} needs an mcts engine", name)),
+        };
+        match name {
+          "iterations" => params.iterations = num(name, value)?,
+          "exploration" => params.exploration = num(name, value)?,
+          _ if value == "random" => params.playout = Playout::Random,
+          _ => params.playout = Playout::Cutoff(num(name, value)?),
+        }
+      }
+      _ => return Err(format!("unknown option '{}'", name)),
+    }
+    Ok(())
+  }
+
+  /// Options that `set_option`, given in order to the default
+  /// config, turns into this one.
+  pub fn options(&self) -> Vec<(&'static str, String)> {
+    fn budget<T: ToString>(value: Option<T>) -> String {
+      value.map_or("none".to_string(), |v| v.to_string())
+    }
+
+    let mut options = vec![];
+    match self.engine {
+      Engine::MinMax => options.push(("engine", "minmax".to_string())),
+      Engine::Mcts(params) => {
+        options.push(("engine", "mcts".to_string()));
+        options.push(("iterations", params.iterations.to_string()));
+        options.push(("playout", match params.playout {
+          Playout::Random => "random".to_string(),
+          Playout::Cutoff(plies) => plies.to_string(),
+        }));
+        options.push(("exploration", params.exploration.to_string()));
+      }
+    }
+    options.push(("depth", self.depth.to_string()));
+    options.push(("beam", self.beam_width.to_string()));
+    options.push(("time-ms", budget(self.time_ms)));
+    options.push(("nodes", budget(self.nodes)));
+    options.push(("evaluator", match self.evaluator {
+      Evaluator::Territory => "territory".to_string(),
+      Evaluator::Endgame => "endgame".to_string(),
+    }));
+    options.push(("seed", self.seed.to_string()));
+    options.push(("noise", self.noise.to_string()));
+    options.push(("blunder", self.blunder.to_string()));
+    options
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn options_round_trip() {
+    let mut configs: Vec<EngineConfig> = [
+      Difficulty::Beginner,
+      Difficulty::Easy,
+      Difficulty::Medium,
+      Difficulty::Hard,
+      Difficulty::Expert,
+    ].iter().map(|d| d.config()).collect();
+    configs.push(EngineConfig {
+      engine: Engine::Mcts(MctsParams { iterations: 77, playout: Playout::Random, exploration: 0.3 }),
+      time_ms: Some(12.5),
+      nodes: Some(99),
+      seed: 42,
+      blunder: 0.125,
+      ..EngineConfig::default()
+    });
+    for config in configs {
+      let mut read = EngineConfig::default();
+      for (name, value) in config.options() {
+        read.set_option(name, &value).unwrap();
+      }
+      assert_eq!(read, config);
+    }
+  }
+
+  #[test]
+  fn bad_options() {
+    let mut config = EngineConfig::default();
+    for &(name, value) in [
+      ("depht", "3"),
+      ("depth", "three"),
+      ("nodes", "-1"),
+      ("evaluator", "random"),
+      ("engine", "alphabeta"),
+      ("difficulty", "impossible"),
+      ("iterations", "100"),
+    ].iter() {
+      assert!(config.set_option(name, value).is_err(), "{}={}", name, value);
+    }
+    assert_eq!(config, EngineConfig::default());
+  }
+}
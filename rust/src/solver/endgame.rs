@@ -0,0 +1,277 @@
+use crate::algo::{LOSS, WIN};
+use crate::board::*;
+use crate::compact_board::*;
+use std::collections::HashMap;
+
+/// Nodes the move counting may search per evaluation inside
+/// the main search, where it runs at every separated leaf.
+pub const EVAL_BUDGET: u64 = 2_000;
+/// Nodes the move counting may search per region when
+/// choosing an endgame move.
+pub const PLAY_BUDGET: u64 = 50_000;
+
+/// Squares connected by king steps through anything but arrows.
+#[derive(Clone, Debug)]
+pub struct Region {
+  /// Empty squares.
+  pub squares: Vec<Pos>,
+  /// Indexes into `CompactBoard::players` of the pieces inside.
+  pub pieces: Vec<usize>,
+  /// The only team with pieces inside, None if there are none
+  /// or both teams are here.
+  pub owner: Option<Team>,
+}
+
+/// Split the board into regions no piece can move or shoot between.
+pub fn regions(board: &CompactBoard) -> Vec<Region> {
+  let (rows, cols) = (board.rows, board.cols);
+  let players: Vec<Player> = board.players().cloned().collect();
+  let piece_at = |p: Pos| players.iter().position(|pl| pl.pos == p);
+  let open = |p: Pos| !board.wall_at(p) || piece_at(p).is_some();
+
+  let mut seen = vec![false; rows as usize * cols as usize];
+  let mut out = Vec::new();
+
+  for r in 0..rows {
+    for c in 0..cols {
+      let start = Pos { row: r, col: c };
+      if seen[start.to_linear(cols)] || !open(start) {
+        continue;
+      }
+
+      let mut region = Region { squares: vec![], pieces: vec![], owner: None };
+      let mut teams = [false; NUM_TEAMS];
+      let mut stack = vec![start];
+      seen[start.to_linear(cols)] = true;
+
+      while let Some(p) = stack.pop() {
+        match piece_at(p) {
+          Some(ix) => {
+            region.pieces.push(ix);
+            teams[players[ix].team.index()] = true;
+          }
+          None => region.squares.push(p),
+        }
+        for dr in -1..=1 {
+          for dc in -1..=1 {
+            let n = Pos { row: p.row + dr, col: p.col + dc };
+            if n.row < 0 || n.col < 0 || n.row >= rows || n.col >= cols {
+              continue;
+            }
+            if !seen[n.to_linear(cols)] && open(n) {
+              seen[n.to_linear(cols)] = true;
+              stack.push(n);
+            }
+          }
+        }
+      }
+
+      let mut present = Team::teams().into_iter().filter(|t| teams[t.index()]);
+      region.owner = match (present.next(), present.next()) {
+        (Some(t), None) => Some(t),
+        _ => None,
+      };
+      out.push(region);
+    }
+  }
+  out
+}
+
+/// Whether no region holds pieces of both teams, so each team
+/// can only use up its own territory.
+pub fn is_separated(regions: &[Region]) -> bool {
+  regions.iter().all(|r| r.owner.is_some() || r.pieces.is_empty())
+}
+
+/// Bounds on how many moves a team can still make. Exact when
+/// `lower == upper`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MoveCount {
+  pub lower: u32,
+  pub upper: u32,
+}
+
+/// Most moves `team` can make in its own regions, searching about
+/// `budget` nodes per region. Only meaningful once `is_separated`.
+pub fn count_moves(board: &CompactBoard, regions: &[Region], team: Team, budget: u64) -> MoveCount {
+  let mut total = MoveCount { lower: 0, upper: 0 };
+  let mut local_board = board.clone();
+  for region in regions.iter().filter(|r| r.owner == Some(team)) {
+    let mut solver = Solver {
+      team,
+      pieces: &region.pieces,
+      memo: HashMap::new(),
+      nodes: 0,
+      budget,
+    };
+    let empty = region.squares.len() as u32;
+    let (found, exact) = solver.max_moves(&mut local_board, empty);
+    total.lower += found;
+    total.upper += if exact { found } else { empty };
+  }
+  total
+}
+
+/// Territory evaluation that, once the teams are separated, replaces
+/// the square count with how many moves each team can really make.
+/// Same perspective and scale as `evaluate_by_queen_bfs_distance`.
+/// Counting moves needs each to fill one square, so other rules
+/// keep the square count.
+pub fn evaluate_with_endgame(board: &CompactBoard, team: Team, cache: &mut DistState) -> i64 {
+  let eval = evaluate_by_queen_bfs_distance(board, team, cache);
+  if board.rules.arrows != 1 || (eval != WIN && eval != LOSS) {
+    return eval;
+  }
+
+  let regions = regions(board);
+  if !is_separated(&regions) {
+    return eval;
+  }
+  let mine = count_moves(board, &regions, team, EVAL_BUDGET);
+  let theirs = count_moves(board, &regions, team.other(), EVAL_BUDGET);
+
+  // more moves wins whoever is to move, equal depends on the turn
+  if mine.lower > theirs.upper {
+    return WIN;
+  }
+  if mine.upper < theirs.lower {
+    return LOSS;
+  }
+  mine.lower as i64 - theirs.lower as i64
+}
+
+/// The move keeping the most moves for `team`, if the teams are
+/// separated. Perfect play when the search fits in `PLAY_BUDGET`,
+/// since the other team cannot interfere. None unless the rules
+/// shoot one arrow per move.
+pub fn endgame_move(board: &CompactBoard, team: Team) -> Option<CompactMove> {
+  if board.rules.arrows != 1 {
+    return None;
+  }
+  let regions = regions(board);
+  if !is_separated(&regions) {
+    return None;
+  }
+
+  // other regions are unaffected by a move, so only compare
+  // what is left in the region the move is made in
+  let mut local_board = board.clone();
+  let mut best: Option<(u32, CompactMove)> = None;
+  for region in regions.iter().filter(|r| r.owner == Some(team)) {
+    let mut solver = Solver {
+      team,
+      pieces: &region.pieces,
+      memo: HashMap::new(),
+      nodes: 0,
+      budget: PLAY_BUDGET,
+    };
+    let empty = region.squares.len() as u32;
+    let moves: Vec<CompactMove> = board
+      .successors(team)
+      .filter(|m| region.pieces.contains(&m.player_ix))
+      .collect();
+
+    for mv in moves {
+      local_board.apply_move(&mv);
+      let (left, _) = solver.max_moves(&mut local_board, empty - 1);
+      local_board.un_apply_move(&mv);
+
+      if best.as_ref().is_none_or(|b| b.0 < left) {
+        best = Some((left, mv));
+      }
+    }
+  }
+  best.map(|b| b.1)
+}
+
+/// Depth-first search for the longest sequence of moves by some
+/// pieces of one team, with nobody else moving.
+struct Solver<'a> {
+  team: Team,
+  pieces: &'a [usize],
+  /// Exact results by board hash.
+  memo: HashMap<u64, u32>,
+  nodes: u64,
+  budget: u64,
+}
+
+impl<'a> Solver<'a> {
+  /// Most moves from here, and whether that is exact. Each move
+  /// fills exactly one square, so `empty` bounds the answer.
+  fn max_moves(&mut self, board: &mut CompactBoard, empty: u32) -> (u32, bool) {
+    if let Some(&found) = self.memo.get(&board.hash()) {
+      return (found, true);
+    }
+
+    let moves: Vec<CompactMove> = board
+      .successors(self.team)
+      .filter(|m| self.pieces.contains(&m.player_ix))
+      .collect();
+
+    let mut best = 0;
+    let mut exact = true;
+    for mv in moves {
+      if best >= empty {
+        break;
+      }
+      if self.nodes >= self.budget {
+        exact = false;
+        break;
+      }
+      self.nodes += 1;
+
+      board.apply_move(&mv);
+      let (found, found_exact) = self.max_moves(board, empty - 1);
+      board.un_apply_move(&mv);
+
+      best = best.max(found + 1);
+      exact = exact && found_exact;
+    }
+
+    let exact = exact || best >= empty;
+    if exact {
+      self.memo.insert(board.hash(), best);
+    }
+    (best, exact)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::notation::Position;
+
+  /// Red on a1 walled into a1-d1 and Blue walled into f6.
+  fn strip() -> CompactBoard {
+    let position: Position = "6x6 R3x1/xxxxxx/6/6/4xx/4xB r".parse().unwrap();
+    CompactBoard::new(&position.board)
+  }
+
+  #[test]
+  fn counts_moves() {
+    let board = strip();
+    let regions = regions(&board);
+    assert!(is_separated(&regions));
+    // stepping along and shooting back fills b1, c1 and d1
+    assert_eq!(count_moves(&board, &regions, Team::Red, PLAY_BUDGET), MoveCount { lower: 3, upper: 3 });
+    assert_eq!(count_moves(&board, &regions, Team::Blue, PLAY_BUDGET), MoveCount { lower: 0, upper: 0 });
+  }
+
+  #[test]
+  fn plays_longest() {
+    let board = strip();
+    // any other move cuts off part of the strip
+    let best: Vec<Move> = vec!["a1-b1/a1".parse().unwrap(), "a1-d1/a1".parse().unwrap()];
+    let mv = endgame_move(&board, Team::Red).unwrap();
+    assert!(best.contains(&Move::from(mv.clone())), "{:?}", mv);
+    assert_eq!(endgame_move(&board, Team::Blue), None);
+
+    let mut after = board.clone();
+    after.apply_move(&mv);
+    let count = count_moves(&after, &regions(&after), Team::Red, PLAY_BUDGET);
+    assert_eq!(count, MoveCount { lower: 2, upper: 2 });
+
+    // nothing to solve while the teams can still meet
+    assert_eq!(endgame_move(&CompactBoard::new(&Board::new()), Team::Red), None);
+  }
+}
@@ -0,0 +1,221 @@
+use crate::board::*;
+
+/// A move and every line played after it.
+#[derive(Clone, Debug)]
+pub struct Variation {
+  pub mv: Move,
+  /// Continuations, the main line first.
+  pub children: Vec<Variation>,
+}
+
+/// Every line tried in one game, and the position being looked at.
+///
+/// Only moves are stored; the caller keeps the board in step by
+/// applying and reverting the moves each method returns.
+#[derive(Clone, Debug)]
+pub struct GameTree {
+  /// First moves of the game, the main line first.
+  root: Vec<Variation>,
+  /// Child indexes from the root to the current position.
+  path: Vec<usize>,
+}
+
+impl GameTree {
+  pub fn new() -> GameTree {
+    GameTree {
+      root: vec![],
+      path: vec![],
+    }
+  }
+
+  /// Moves from the start of the game to the current position.
+  pub fn ply(&self) -> usize {
+    self.path.len()
+  }
+
+  /// Continuations of the current position, the main line first.
+  pub fn variations(&self) -> &[Variation] {
+    self.children_at(&self.path)
+  }
+
+  /// Moves left on the main line from the current position.
+  pub fn mainline_remaining(&self) -> usize {
+    let mut n = 0;
+    let mut level = self.variations();
+    while let Some(first) = level.first() {
+      n += 1;
+      level = &first.children;
+    }
+    n
+  }
+
+  /// Moves from the start of the game to the current position,
+  /// then on along the main line to its end.
+  pub fn line(&self) -> Vec<Move> {
+    let mut moves = vec![];
+    let mut level = &self.root;
+    for &i in &self.path {
+      moves.push(level[i].mv);
+      level = &level[i].children;
+    }
+    while let Some(first) = level.first() {
+      moves.push(first.mv);
+      level = &first.children;
+    }
+    moves
+  }
+
+  /// Record `mv` from the current position and move to it. A move
+  /// already in the tree is followed, any other starts a new
+  /// variation after the existing ones.
+  pub fn play(&mut self, mv: Move) {
+    let children = self.children_at_mut();
+    let i = match children.iter().position(|v| v.mv == mv) {
+      Some(i) => i,
+      None => {
+        children.push(Variation { mv, children: vec![] });
+        children.len() - 1
+      }
+    };
+    self.path.push(i);
+  }
+
+  /// Step back one move, returning it so it can be reverted.
+  pub fn back(&mut self) -> Option<Move> {
+    let last = self.path.pop()?;
+    Some(self.children_at(&self.path)[last].mv)
+  }
+
+  /// Step into variation `index` of the current position,
+  /// returning its move so it can be applied.
+  pub fn forward(&mut self, index: usize) -> Option<Move> {
+    let mv = self.variations().get(index)?.mv;
+    self.path.push(index);
+    Some(mv)
+  }
+
+  /// Make variation `index` of the current position its main line.
+  ///
+  /// Return false if there is no such variation.
+  pub fn promote(&mut self, index: usize) -> bool {
+    let children = self.children_at_mut();
+    if index >= children.len() {
+      return false;
+    }
+    let v = children.remove(index);
+    children.insert(0, v);
+    true
+  }
+
+  /// Remove variation `index` of the current position and everything
+  /// after it. The next variation takes its place.
+  ///
+  /// Return false if there is no such variation.
+  pub fn delete(&mut self, index: usize) -> bool {
+    let children = self.children_at_mut();
+    if index >= children.len() {
+      return false;
+    }
+    children.remove(index);
+    true
+  }
+
+  fn children_at(&self, path: &[usize]) -> &[Variation] {
+    let mut level = &self.root;
+    for &i in path {
+      level = &level[i].children;
+    }
+    level
+  }
+
+  fn children_at_mut(&mut self) -> &mut Vec<Variation> {
+    let mut level = &mut self.root;
+    for &i in &self.path {
+      level = &mut level[i].children;
+    }
+    level
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn mv(s: &str) -> Move {
+    s.parse().unwrap()
+  }
+
+  fn moves(variations: &[Variation]) -> Vec<Move> {
+    variations.iter().map(|v| v.mv).collect()
+  }
+
+  /// Main line a1-a2/a3 b1-b2/b3, with c1-c2/c3 as a second first
+  /// move and d1-d2/d3 after it. The current position is the end
+  /// of the second variation.
+  fn tree() -> GameTree {
+    let mut tree = GameTree::new();
+    tree.play(mv("a1-a2/a3"));
+    tree.play(mv("b1-b2/b3"));
+    tree.back();
+    tree.back();
+    tree.play(mv("c1-c2/c3"));
+    tree.play(mv("d1-d2/d3"));
+    tree
+  }
+
+  #[test]
+  fn lists_variations() {
+    let mut tree = tree();
+    assert_eq!(tree.ply(), 2);
+    assert_eq!(tree.line(), vec![mv("c1-c2/c3"), mv("d1-d2/d3")]);
+    assert!(tree.variations().is_empty());
+
+    assert_eq!(tree.back(), Some(mv("d1-d2/d3")));
+    assert_eq!(tree.back(), Some(mv("c1-c2/c3")));
+    assert_eq!(tree.back(), None);
+    assert_eq!(moves(tree.variations()), vec![mv("a1-a2/a3"), mv("c1-c2/c3")]);
+    assert_eq!(tree.line(), vec![mv("a1-a2/a3"), mv("b1-b2/b3")]);
+    assert_eq!(tree.mainline_remaining(), 2);
+
+    // playing a move already in the tree follows it
+    tree.play(mv("c1-c2/c3"));
+    assert_eq!(tree.back(), Some(mv("c1-c2/c3")));
+    assert_eq!(tree.variations().len(), 2);
+    assert_eq!(tree.forward(2), None);
+    assert_eq!(tree.forward(1), Some(mv("c1-c2/c3")));
+    assert_eq!(tree.mainline_remaining(), 1);
+  }
+
+  #[test]
+  fn promotes() {
+    let mut tree = tree();
+    tree.back();
+    tree.back();
+    assert!(!tree.promote(2));
+    assert!(tree.promote(1));
+    assert_eq!(moves(tree.variations()), vec![mv("c1-c2/c3"), mv("a1-a2/a3")]);
+    assert_eq!(tree.line(), vec![mv("c1-c2/c3"), mv("d1-d2/d3")]);
+    assert_eq!(tree.forward(1), Some(mv("a1-a2/a3")));
+    assert_eq!(tree.variations()[0].mv, mv("b1-b2/b3"));
+  }
+
+  #[test]
+  fn deletes() {
+    let mut tree = tree();
+    tree.back();
+    assert!(!tree.delete(1));
+    // the branch just left, holding the position looked at before
+    assert!(tree.delete(0));
+    assert!(tree.variations().is_empty());
+    assert_eq!(tree.ply(), 1);
+    assert_eq!(tree.line(), vec![mv("c1-c2/c3")]);
+
+    assert_eq!(tree.back(), Some(mv("c1-c2/c3")));
+    assert!(tree.delete(0));
+    assert_eq!(moves(tree.variations()), vec![mv("c1-c2/c3")]);
+    assert_eq!(tree.line(), vec![mv("c1-c2/c3")]);
+    assert!(tree.delete(0));
+    assert!(tree.line().is_empty());
+    assert!(!tree.delete(0));
+  }
+}
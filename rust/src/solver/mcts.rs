@@ -0,0 +1,230 @@
+use crate::algo::SearchInfo;
+use crate::board::*;
+use crate::compact_board::*;
+use crate::config::*;
+use crate::rng::Rng;
+use crate::rules::Shots;
+use crate::utils::now_ms;
+
+/// Longest random playout in a game without arrows, which need
+/// not end, before it is scored like a `Playout::Cutoff`.
+const MAX_RANDOM_PLIES: u32 = 200;
+
+/// How a new leaf is scored.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Playout {
+  /// Play random moves until a team cannot move, or without
+  /// arrows until `MAX_RANDOM_PLIES`.
+  Random,
+  /// Play this many random moves, then score the position
+  /// with `EngineConfig::evaluate`.
+  Cutoff(u32),
+}
+
+/// Tuning for `mcts`. The time budget and evaluator
+/// come from the `EngineConfig`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MctsParams {
+  /// Number of playouts.
+  pub iterations: u64,
+  pub playout: Playout,
+  /// UCT exploration constant.
+  pub exploration: f64,
+}
+impl Default for MctsParams {
+  fn default() -> MctsParams {
+    MctsParams {
+      iterations: 2000,
+      playout: Playout::Cutoff(4),
+      exploration: 1.4,
+    }
+  }
+}
+
+struct Node {
+  /// Move from the parent, made by `mover`.
+  mv: Option<CompactMove>,
+  mover: Team,
+  parent: Option<usize>,
+  children: Vec<usize>,
+  untried: Vec<CompactMove>,
+  visits: f64,
+  /// Sum of rewards for `mover`, 1 for a win and 0 for a loss.
+  wins: f64,
+}
+
+/// Monte Carlo tree search with UCT selection.
+///
+/// The principal variation follows the most visited moves, and the
+/// score is the first one's win rate for `team` in per mille. Nodes
+/// counts playouts, and depth is the length of the variation.
+pub fn mcts(cache: &mut DistState, rng: &mut Rng, board: &CompactBoard, team: Team, params: MctsParams, config: &EngineConfig) -> SearchInfo {
+  let start_ms = now_ms();
+  let mut nodes = vec![Node {
+    mv: None,
+    mover: team.other(),
+    parent: None,
+    children: vec![],
+    untried: board.successors(team).collect(),
+    visits: 0.0,
+    wins: 0.0,
+  }];
+
+  let mut iteration = 0;
+  while iteration < params.iterations.max(1) {
+    if let Some(time_ms) = config.time_ms {
+      if iteration > 0 && now_ms() - start_ms >= time_ms {
+        break;
+      }
+    }
+    iteration += 1;
+
+    let mut local_board = board.clone();
+    let mut ix = 0;
+
+    // selection
+    while nodes[ix].untried.is_empty() && !nodes[ix].children.is_empty() {
+      let parent_visits = nodes[ix].visits;
+      let mut best = nodes[ix].children[0];
+      let mut best_uct = f64::NEG_INFINITY;
+      for &c in &nodes[ix].children {
+        let child = &nodes[c];
+        let uct = child.wins / child.visits
+          + params.exploration * (parent_visits.ln() / child.visits).sqrt();
+        if uct > best_uct {
+          best_uct = uct;
+          best = c;
+        }
+      }
+      ix = best;
+      if let Some(mv) = &nodes[ix].mv {
+        local_board.apply_move(mv);
+      }
+    }
+
+    // expansion
+    if !nodes[ix].untried.is_empty() {
+      let pick = rng.below(nodes[ix].untried.len());
+      let mv = nodes[ix].untried.swap_remove(pick);
+      let mover = nodes[ix].mover.other();
+      local_board.apply_move(&mv);
+      nodes.push(Node {
+        mv: Some(mv),
+        mover,
+        parent: Some(ix),
+        children: vec![],
+        untried: local_board.successors(mover.other()).collect(),
+        visits: 0.0,
+        wins: 0.0,
+      });
+      let child = nodes.len() - 1;
+      nodes[ix].children.push(child);
+      ix = child;
+    }
+
+    // simulation
+    let to_move = nodes[ix].mover.other();
+    let reward = playout(cache, rng, &mut local_board, to_move, params.playout, config);
+
+    // backpropagation, `reward` is for `to_move`
+    let mut walk = Some(ix);
+    while let Some(i) = walk {
+      nodes[i].visits += 1.0;
+      if nodes[i].mover == to_move {
+        nodes[i].wins += reward;
+      } else {
+        nodes[i].wins += 1.0 - reward;
+      }
+      walk = nodes[i].parent;
+    }
+  }
+
+  let most_visited = |ix: usize| {
+    nodes[ix]
+      .children
+      .iter()
+      .cloned()
+      .max_by(|&a, &b| nodes[a].visits.partial_cmp(&nodes[b].visits).unwrap())
+  };
+  let mut pv = vec![];
+  let mut walk = most_visited(0);
+  while let Some(c) = walk {
+    pv.extend(nodes[c].mv.clone());
+    walk = most_visited(c);
+  }
+  let score = match most_visited(0) {
+    Some(c) => (nodes[c].wins / nodes[c].visits * 1000.0) as i64,
+    None => 0,
+  };
+
+  SearchInfo {
+    depth: pv.len() as i32,
+    pv,
+    score,
+    nodes: iteration,
+    time_ms: now_ms() - start_ms,
+  }
+}
+
+/// Finish a game from `board`, or play until `kind` scores it,
+/// and return the reward for `team`.
+fn playout(cache: &mut DistState, rng: &mut Rng, board: &mut CompactBoard, team: Team, kind: Playout, config: &EngineConfig) -> f64 {
+  let limit = match kind {
+    Playout::Cutoff(limit) => limit,
+    Playout::Random if board.rules.arrows == 0 => MAX_RANDOM_PLIES,
+    Playout::Random => u32::MAX,
+  };
+  let mut to_move = team;
+  let mut plies = 0;
+  loop {
+    if plies >= limit {
+      let score = config.evaluate(board, to_move, cache);
+      let reward = if score > 0 {
+        1.0
+      } else if score < 0 {
+        0.0
+      } else {
+        0.5
+      };
+      return if to_move == team { reward } else { 1.0 - reward };
+    }
+
+    match random_move(rng, board, to_move) {
+      Some(mv) => board.apply_move(&mv),
+      None => return if to_move == team { 0.0 } else { 1.0 },
+    }
+    to_move = to_move.other();
+    plies += 1;
+  }
+}
+
+/// A cheap, not quite uniform, random legal move.
+fn random_move(rng: &mut Rng, board: &CompactBoard, team: Team) -> Option<CompactMove> {
+  let mut pieces: Vec<(usize, Player)> = board
+    .players()
+    .cloned()
+    .enumerate()
+    .filter(|(_, p)| p.team == team)
+    .collect();
+
+  while !pieces.is_empty() {
+    let (player_ix, player) = pieces.swap_remove(rng.below(pieces.len()));
+    let mut dests: Vec<Pos> = board.rules.piece_moves(board, player.pos).collect();
+    while !dests.is_empty() {
+      let new_pos = dests.swap_remove(rng.below(dests.len()));
+      // with one arrow never empty, the piece can always shoot
+      // back towards where it came from
+      let shots: Vec<Shots> = board.rules.shot_sets(board, new_pos, player.pos).collect();
+      if shots.is_empty() {
+        continue;
+      }
+      return Some(CompactMove {
+        player_ix,
+        old_pos: player.pos,
+        new_pos,
+        shots: shots[rng.below(shots.len())],
+      });
+    }
+  }
+  None
+}
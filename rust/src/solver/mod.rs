@@ -1,13 +1,69 @@
 pub mod algo;
 pub mod board;
 pub mod compact_board;
+pub mod config;
+pub mod endgame;
+pub mod game_tree;
+pub mod mcts;
+pub mod notation;
+pub mod paranoid;
+pub mod record;
+pub mod rng;
+pub mod rules;
+pub mod transposition;
 
+use algo::{SearchInfo, StepProgress, SteppedSearch};
 use board::*;
 use compact_board::*;
+use config::*;
+use game_tree::{GameTree, Variation};
+use notation::{NotationError, Position};
+use record::{GameRecord, RecordError};
+use rng::Rng;
+use rules::Rules;
+use transposition::TranspositionTable;
 
-enum HistoryMove {
-  NewGame(Board),
-  Move(Move),
+/// Default transposition table size, kept small for wasm.
+const DEFAULT_TT_BYTES: usize = 4 << 20;
+
+/// Most moves `self_play` makes, as games without arrows need not end.
+const MAX_SELF_PLAY_PLIES: usize = 500;
+
+#[cfg(not(target_arch = "wasm32"))]
+fn default_threads() -> usize {
+  std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1)
+}
+
+#[cfg(target_arch = "wasm32")]
+fn default_threads() -> usize {
+  1
+}
+
+/// Whether a game is still being played.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GameStatus {
+  InProgress,
+  /// Every other team is out, having had no move on its turn.
+  Winner(Team),
+}
+
+/// A game put aside by `new_game` or by undoing past the start of
+/// the next one, with the position it was left at.
+struct SavedGame {
+  board: Board,
+  turn: Team,
+  out: Vec<(usize, Team)>,
+  tree: GameTree,
+}
+
+/// An `ai_move` being computed by `search_step`.
+enum PendingSearch {
+  Stepped(Box<SteppedSearch>),
+  /// Chosen without searching, played by the first step.
+  Quick(CompactMove),
+  /// Games of more than two teams and Monte Carlo searches are
+  /// searched all at once by the first step.
+  Whole,
 }
 
 /// Data structures for amazon simulation,
@@ -15,8 +71,22 @@ enum HistoryMove {
 pub struct Amazons {
   pub turn: Team,
   pub current: Board,
-  history: Vec<HistoryMove>,
-  cache: DistState,
+  /// Teams out of the game, each with the ply of the current game
+  /// at which its turn came and it had no move.
+  out: Vec<(usize, Team)>,
+  /// Moves and variations of the game being played.
+  game: GameTree,
+  /// Games before this one, the most recent last.
+  earlier_games: Vec<SavedGame>,
+  /// Games after this one left by undoing, the next last.
+  later_games: Vec<SavedGame>,
+  /// One scratch buffer per search thread.
+  caches: Vec<DistState>,
+  tt: TranspositionTable,
+  rng: Rng,
+  /// How `ai_move` searches.
+  config: EngineConfig,
+  stepper: Option<PendingSearch>,
 }
 
 impl Amazons {
@@ -25,72 +95,656 @@ impl Amazons {
     Amazons {
       turn: Team::Red,
       current: Board::new(),
-      history: vec![],
-      cache: DistState::new(),
+      out: vec![],
+      game: GameTree::new(),
+      earlier_games: vec![],
+      later_games: vec![],
+      caches: vec![DistState::new(); default_threads()],
+      tt: TranspositionTable::with_memory(DEFAULT_TT_BYTES),
+      rng: Rng::new(0),
+      config: EngineConfig::default(),
+      stepper: None,
     }
   }
 
-  pub fn new_game(&mut self) {
-    self.history.push(HistoryMove::NewGame(self.current.clone()));
-    self.current = Board::new();
-    self.turn = Team::Red;
+  /// The side to move followed by `Board::to_bytes`,
+  /// compact enough to post to a Web Worker.
+  ///
+  /// The first byte is the `Team::index` of the side to move,
+  /// plus `16 << i` for each team `i` that is out.
+  pub fn position_bytes(&self) -> Vec<u8> {
+    let out = self.out.iter().fold(0, |mask, (_, t)| mask | 16 << t.index());
+    let mut v = vec![self.turn.index() as u8 | out];
+    v.extend(self.current.to_bytes());
+    v
+  }
+
+  /// Replace the game with the position from `position_bytes`,
+  /// keeping the search settings and tables.
+  ///
+  /// Return false, changing nothing, if the bytes are malformed.
+  pub fn load_position_bytes(&mut self, bytes: &[u8]) -> bool {
+    if bytes.is_empty() {
+      return false;
+    }
+    let turn = match Team::teams().get((bytes[0] & 15) as usize) {
+      Some(t) => *t,
+      None => return false,
+    };
+    let board = match Board::from_bytes(&bytes[1..]) {
+      Some(b) => b,
+      None => return false,
+    };
+    let teams = board.teams();
+    if board.check_position().is_err() || !teams.contains(&turn) || bytes[0] & 16 << turn.index() != 0 {
+      return false;
+    }
+
+    self.current = board;
+    self.out = teams.into_iter()
+      .filter(|t| bytes[0] & 16 << t.index() != 0)
+      .map(|t| (0, t))
+      .collect();
+    self.game = GameTree::new();
+    self.earlier_games.clear();
+    self.later_games.clear();
+    self.stepper = None;
+    self.begin_turn(turn);
+    true
+  }
+
+  /// The position as a string, see `Position`.
+  pub fn position_string(&self) -> String {
+    let mut out: Vec<Team> = self.out.iter().map(|(_, t)| *t).collect();
+    out.sort_by_key(|t| t.index());
+    let position = Position { board: self.current.clone(), turn: self.turn, out };
+    position.to_string()
   }
 
-  /// Revert the last move.
-  pub fn undo_move(&mut self) {
-    match self.history.pop() {
-      Some(HistoryMove::NewGame(b)) => {
-        self.current = b;
-        self.turn = Team::Red;
+  /// Start a game from a position string, see `Position`, as
+  /// `start_position` does.
+  pub fn load_position(&mut self, s: &str) -> Result<(), NotationError> {
+    let position: Position = s.parse()?;
+    self.start_position(position.board, position.turn, &position.out)?;
+    Ok(())
+  }
+
+  /// The current game to save, with `headers` such as the players
+  /// and date: its start, and the moves to the current position and
+  /// on along the main line. Variations are left out.
+  pub fn game_record(&self, headers: Vec<(String, String)>) -> GameRecord {
+    let moves = self.game.line();
+    let ply = self.ply();
+
+    let mut board = self.current.clone();
+    for mv in moves[..ply].iter().rev() {
+      board.un_apply_move(*mv);
+    }
+    let mut turn = self.turn;
+    if let Some(mv) = moves.first() {
+      if let BoardSlot::Piece(t) = board.at(mv.old_pos) {
+        turn = *t;
       }
-      Some(HistoryMove::Move(m)) => {
-        self.current.un_apply_move(m);
-        self.turn = self.turn.other();
+    }
+    let start = Position {
+      board,
+      turn,
+      out: self.out.iter().filter(|(at, _)| *at == 0).map(|(_, t)| *t).collect(),
+    };
+
+    // play on to the end of the main line for the result
+    let (mut board, mut out, mut turn) = (self.current.clone(), self.out.clone(), self.turn);
+    for (i, mv) in moves.iter().enumerate().skip(ply) {
+      board.apply_move(*mv);
+      turn = pass_turn(&board, &mut out, i + 1, turn);
+    }
+    let result = if alive_teams(&board, &out).len() > 1 { None } else { Some(turn) };
+    GameRecord { headers, start, moves, result }
+  }
+
+  /// Start a game from `record`, as `start_position` does, and play
+  /// its moves, so that `undo_move` steps back through them.
+  ///
+  /// Return Err saying why, leaving the old game as it was, if the
+  /// start cannot be played from, a move is illegal or the moves
+  /// do not end with the record's result.
+  pub fn load_record(&mut self, record: &GameRecord) -> Result<(), RecordError> {
+    let later = std::mem::take(&mut self.later_games);
+    let start = record.start.clone();
+    if let Err(e) = self.start_position(start.board, start.turn, &start.out) {
+      self.later_games = later;
+      return Err(RecordError::BadStart(e.into()));
+    }
+    let mut played = Ok(());
+    for (i, mv) in record.moves.iter().enumerate() {
+      played = match self.status() {
+        GameStatus::Winner(_) => Err(RecordError::GameOver(i + 1)),
+        GameStatus::InProgress => self.player_move(*mv).map_err(|e| RecordError::IllegalMove(i + 1, *mv, e)),
+      };
+      if played.is_err() {
+        break;
+      }
+    }
+    if played.is_ok() {
+      let result = match self.status() {
+        GameStatus::Winner(t) => Some(t),
+        GameStatus::InProgress => None,
+      };
+      if result != record.result {
+        played = Err(RecordError::WrongResult(result));
       }
-      None => {}
     }
+    if played.is_err() {
+      // back to the old game, as if it had never been left
+      let earlier = self.earlier_games.pop().unwrap();
+      self.swap_game(earlier);
+      self.later_games = later;
+    }
+    played
+  }
+
+  /// Start a game on a board of `rows` by `cols` for `teams`
+  /// teams with `amazons` each, see `Board::starting`, played
+  /// by `rules`.
+  ///
+  /// Return false, changing nothing, if there is no such setup.
+  pub fn new_game(&mut self, rows: usize, cols: usize, teams: usize, amazons: usize, rules: Rules) -> bool {
+    let mut board = match Board::starting(rows, cols, teams, amazons) {
+      Some(b) => b,
+      None => return false,
+    };
+    board.rules = rules;
+    self.start_position(board, Team::Red, &[]).is_ok()
+  }
+
+  /// Start a game from any position with `turn` to move and the
+  /// teams in `out` already out, such as one set up for a puzzle.
+  /// Like `new_game`, `undo_move` from its start goes back to the
+  /// game before.
+  pub fn start_position(&mut self, board: Board, turn: Team, out: &[Team]) -> Result<(), PositionError> {
+    board.check_position()?;
+    if !board.teams().contains(&turn) {
+      return Err(PositionError::NoPieces(turn));
+    }
+    if out.contains(&turn) {
+      return Err(PositionError::OutToMove(turn));
+    }
+    self.stepper = None;
+    self.later_games.clear();
+    let earlier = self.swap_game(SavedGame {
+      board,
+      turn,
+      out: out.iter().map(|t| (0, *t)).collect(),
+      tree: GameTree::new(),
+    });
+    self.earlier_games.push(earlier);
+    self.tt.clear();
+    self.begin_turn(turn);
+    Ok(())
+  }
+
+  pub fn config(&self) -> &EngineConfig {
+    &self.config
+  }
+
+  /// Change how `ai_move` searches, restarting its
+  /// random numbers from `config.seed`.
+  pub fn set_config(&mut self, config: EngineConfig) {
+    self.stepper = None;
+    self.rng = Rng::new(config.seed);
+    self.config = config;
+  }
+
+  /// Resize the transposition table, discarding its contents.
+  pub fn set_tt_memory(&mut self, bytes: usize) {
+    self.tt = TranspositionTable::with_memory(bytes);
+  }
+
+  /// Revert the last move, which `redo_move` can replay. At the
+  /// start of a game, go back to where the previous game was left.
+  ///
+  /// Return false if there is nothing to undo.
+  pub fn undo_move(&mut self) -> bool {
+    self.stepper = None;
+    let ply = self.game.ply();
+    if let Some(m) = self.game.back() {
+      while self.out.last().is_some_and(|(at, _)| *at == ply) {
+        self.out.pop();
+      }
+      self.current.un_apply_move(m);
+      if let BoardSlot::Piece(t) = self.current.at(m.old_pos) {
+        self.turn = *t;
+      }
+      return true;
+    }
+    match self.earlier_games.pop() {
+      Some(earlier) => {
+        let later = self.swap_game(earlier);
+        self.later_games.push(later);
+        true
+      }
+      None => false,
+    }
+  }
+
+  /// Replay the main line move after the current position. At the
+  /// end of a game left by undoing, go back to the next game.
+  ///
+  /// Return false if there is nothing to redo.
+  pub fn redo_move(&mut self) -> bool {
+    if self.play_variation(0) {
+      return true;
+    }
+    match self.later_games.pop() {
+      Some(later) => {
+        self.stepper = None;
+        let earlier = self.swap_game(later);
+        self.earlier_games.push(earlier);
+        true
+      }
+      None => false,
+    }
+  }
+
+  fn swap_game(&mut self, game: SavedGame) -> SavedGame {
+    SavedGame {
+      board: std::mem::replace(&mut self.current, game.board),
+      turn: std::mem::replace(&mut self.turn, game.turn),
+      out: std::mem::replace(&mut self.out, game.out),
+      tree: std::mem::replace(&mut self.game, game.tree),
+    }
+  }
+
+  /// Moves played in the current game so far.
+  pub fn ply(&self) -> usize {
+    self.game.ply()
+  }
+
+  /// Moves on the current game's main line from here, plus
+  /// those played to get here.
+  pub fn game_length(&self) -> usize {
+    self.game.ply() + self.game.mainline_remaining()
+  }
+
+  /// Step back through the current game, or forward along its
+  /// main line, to just after its `ply`th move, stopping at
+  /// either end.
+  pub fn goto_ply(&mut self, ply: usize) {
+    while self.game.ply() > ply {
+      self.undo_move();
+    }
+    while self.game.ply() < ply && self.play_variation(0) {}
+  }
+
+  /// Go to the start of the current game.
+  pub fn first(&mut self) {
+    self.goto_ply(0);
+  }
+
+  /// Go to the end of the current game's main line.
+  pub fn last(&mut self) {
+    self.goto_ply(self.game_length());
+  }
+
+  /// Moves tried from the current position, the main line first.
+  pub fn variations(&self) -> &[Variation] {
+    self.game.variations()
+  }
+
+  /// Play the move of variation `index` from `variations`.
+  ///
+  /// Return false if there is no such variation.
+  pub fn play_variation(&mut self, index: usize) -> bool {
+    match self.game.forward(index) {
+      Some(m) => {
+        self.stepper = None;
+        self.current.apply_move(m);
+        self.pass_turn();
+        true
+      }
+      None => false,
+    }
+  }
+
+  /// Make variation `index` from `variations` the main line.
+  pub fn promote_variation(&mut self, index: usize) -> bool {
+    self.game.promote(index)
+  }
+
+  /// Forget variation `index` from `variations` and all moves after it.
+  pub fn delete_variation(&mut self, index: usize) -> bool {
+    self.game.delete(index)
+  }
+
+  /// Over once only one team is left, the others being out for
+  /// having had no legal move on their turn.
+  pub fn status(&self) -> GameStatus {
+    if self.alive().len() > 1 {
+      return GameStatus::InProgress;
+    }
+    GameStatus::Winner(self.turn)
+  }
+
+  /// Teams still in the game, in turn order.
+  pub fn alive(&self) -> Vec<Team> {
+    alive_teams(&self.current, &self.out)
+  }
+
+  /// The side to move has just moved: give the turn to the
+  /// next team in the game.
+  fn pass_turn(&mut self) {
+    self.turn = pass_turn(&self.current, &mut self.out, self.game.ply(), self.turn);
+  }
+
+  /// Give the turn to `team`, or the next team that can move.
+  fn begin_turn(&mut self, team: Team) {
+    self.turn = begin_turn(&self.current, &mut self.out, self.game.ply(), team);
+  }
+
+  /// Whether only two teams, which are each other's `Team::other`,
+  /// are left. Other games need the paranoid search.
+  fn two_teams(&self) -> bool {
+    let alive = self.alive();
+    alive.len() == 2 && alive[0].other() == alive[1]
   }
 
   /// Try to record a player's move
   ///
-  /// Return Err(msg) explaining the error if the move is invalid.
-  pub fn player_move(&mut self, mv: Move) {
-    self.turn = self.turn.other();
+  /// Return Err explaining the error, changing nothing, if the
+  /// move is invalid.
+  pub fn player_move(&mut self, mv: Move) -> Result<(), MoveError> {
+    self.current.check_move(self.turn, mv)?;
+    self.stepper = None;
     self.current.apply_move(mv);
-    self.history.push(HistoryMove::Move(mv));
+    self.record(mv);
+    self.pass_turn();
+    Ok(())
   }
 
-  /// Compute and make a move for an AI team.
+  /// Compute and make a move for an AI team, returning
+  /// what the search found. The move is the first of its `pv`.
   ///
-  /// Return false if the AI gives up.
-  pub fn ai_move(&mut self) -> Option<Move> {
-    // TODO Multi-threading based on # of caches
-    let cache = &mut self.cache;
-    return match algo::min_max(cache, &CompactBoard::new(&self.current), self.turn, 3) {
-      (Some(compact_move), _) => {
-        self.turn = self.turn.other();
-        let m: Move = compact_move.into();
-        self.current.apply_move(m.clone());
-        self.history.push(HistoryMove::Move(m.clone()));
-        Some(m)
+  /// Return None if the AI gives up.
+  pub fn ai_move(&mut self) -> Option<SearchInfo> {
+    self.stepper = None;
+    let info = self.search(self.config);
+    let m = info.best()?.clone();
+    self.play_ai_move(m);
+    Some(info)
+  }
+
+  /// The move the AI recommends for the side to move, without
+  /// playing it. Searches like `ai_move` but never deliberately
+  /// weakened by `noise` or `blunder`.
+  ///
+  /// Return None if there is no move.
+  pub fn suggest_move(&mut self) -> Option<SearchInfo> {
+    let config = EngineConfig {
+      noise: 0,
+      blunder: 0.0,
+      ..self.config
+    };
+    // on a copy of the random numbers, so hints leave the AI's moves alone
+    let rng = self.rng.clone();
+    let info = self.search(config);
+    self.rng = rng;
+    if info.pv.is_empty() { None } else { Some(info) }
+  }
+
+  /// The `k` best moves for the side to move with their scores and
+  /// expected lines, best first, by the alpha-beta search within
+  /// the current config's depth and budgets. Nothing is played.
+  ///
+  /// Games of more than two teams only get the best move.
+  pub fn analyze(&mut self, k: usize) -> Vec<SearchInfo> {
+    let config = EngineConfig {
+      noise: 0,
+      blunder: 0.0,
+      ..self.config
+    };
+    if !self.two_teams() {
+      let info = self.search(config);
+      return if info.pv.is_empty() { vec![] } else { vec![info] };
+    }
+    let board = CompactBoard::new(&self.current);
+    algo::multi_pv(&mut self.caches[0], Some(&mut self.tt), &board, self.turn, config, k)
+  }
+
+  /// Games of more than two teams always use the paranoid search,
+  /// ignoring the engine, evaluator, noise and blunders.
+  fn search(&mut self, config: EngineConfig) -> SearchInfo {
+    let board = CompactBoard::new(&self.current);
+    if !self.two_teams() {
+      let alive = self.alive();
+      return paranoid::paranoid(&mut self.caches[0], &board, self.turn, &alive, config);
+    }
+    match self.quick_move(&board, &config) {
+      Some(m) => unsearched(m),
+      None => match config.engine {
+        Engine::MinMax => self.alpha_beta_search(&board, config),
+        Engine::Mcts(params) => {
+          mcts::mcts(&mut self.caches[0], &mut self.rng, &board, self.turn, params, &config)
+        }
+      },
+    }
+  }
+
+  /// A move for a two team game chosen without searching: a blunder,
+  /// or with the endgame evaluator, a solved endgame.
+  fn quick_move(&mut self, board: &CompactBoard, config: &EngineConfig) -> Option<CompactMove> {
+    match self.blunder(board, config) {
+      None if config.evaluator == Evaluator::Endgame => endgame::endgame_move(board, self.turn),
+      found => found,
+    }
+  }
+
+  fn play_ai_move(&mut self, compact_move: CompactMove) -> Move {
+    let m: Move = compact_move.into();
+    self.current.apply_move(m);
+    self.record(m);
+    self.pass_turn();
+    m
+  }
+
+  /// Add a move just played to the game tree. A move other than
+  /// those already tried here starts a new variation.
+  fn record(&mut self, mv: Move) {
+    self.later_games.clear();
+    self.game.play(mv);
+  }
+
+  /// With probability `config.blunder`, a random move from the
+  /// beam of statically best moves.
+  fn blunder(&mut self, board: &CompactBoard, config: &EngineConfig) -> Option<CompactMove> {
+    if config.blunder <= 0.0 || !self.two_teams() || self.rng.unit() >= config.blunder {
+      return None;
+    }
+    let mut local_board = board.clone();
+    let (cache, team) = (&mut self.caches[0], self.turn);
+    let mut beam = algo::top_n(config.beam_width, board.successors(team).map(|m| {
+      local_board.apply_move(&m);
+      let eval = config.evaluate(&local_board, team, cache);
+      local_board.un_apply_move(&m);
+      (eval, m)
+    }));
+    if beam.is_empty() {
+      return None;
+    }
+    let pick = self.rng.below(beam.len());
+    Some(beam.swap_remove(pick).1)
+  }
+
+  fn alpha_beta_search(&mut self, board: &CompactBoard, config: EngineConfig) -> SearchInfo {
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+      if self.caches.len() > 1 {
+        return algo::parallel_iterative_deepening(&mut self.caches, Some(&mut self.tt), board, self.turn, config);
       }
-      (None, _) => None,
+    }
+    algo::iterative_deepening(&mut self.caches[0], Some(&mut self.tt), board, self.turn, config)
+  }
+
+  /// Begin an `ai_move` that is computed a little at a time by
+  /// `search_step`, so single-threaded callers can do other work in
+  /// between. Only the alpha-beta search is stepped; others run
+  /// whole in the first step. Any earlier stepped search is dropped.
+  pub fn start_search(&mut self) {
+    if !self.two_teams() || self.config.engine != Engine::MinMax {
+      self.stepper = Some(PendingSearch::Whole);
+      return;
+    }
+    let board = CompactBoard::new(&self.current);
+    let config = self.config;
+    self.stepper = Some(match self.quick_move(&board, &config) {
+      Some(m) => PendingSearch::Quick(m),
+      None => PendingSearch::Stepped(Box::new(SteppedSearch::new(&board, self.turn, config))),
+    });
+  }
+
+  /// Advance the search from `start_search` by about `node_budget`
+  /// positions. Once the progress says it is done, its move has
+  /// been played.
+  ///
+  /// Return None if no search is running, including after any
+  /// other move, undo or new game.
+  pub fn search_step(&mut self, node_budget: u64) -> Option<StepProgress> {
+    let progress = match self.stepper.as_mut()? {
+      PendingSearch::Stepped(stepper) => stepper.search_step(Some(&mut self.tt), node_budget),
+      PendingSearch::Quick(m) => StepProgress { info: unsearched(m.clone()), done: true },
+      PendingSearch::Whole => StepProgress { info: self.search(self.config), done: true },
     };
+    if progress.done {
+      self.stepper = None;
+      if let Some(compact_move) = progress.info.best().cloned() {
+        self.play_ai_move(compact_move);
+      }
+    }
+    Some(progress)
+  }
+
+  /// Let `configs[i]` play for the team with `Team::index` i from
+  /// the current position until only one team is left, and return
+  /// the winner. Configs are reused if there are fewer than teams.
+  ///
+  /// Return None if an AI gives up or `MAX_SELF_PLAY_PLIES` moves
+  /// are made without a winner.
+  pub fn self_play(&mut self, configs: &[EngineConfig]) -> Option<Team> {
+    let saved = self.config;
+    for _ in 0..MAX_SELF_PLAY_PLIES {
+      if self.status() != GameStatus::InProgress {
+        break;
+      }
+      self.config = configs[self.turn.index() % configs.len()];
+      if self.ai_move().is_none() {
+        break;
+      }
+    }
+    self.config = saved;
+    match self.status() {
+      GameStatus::Winner(t) => Some(t),
+      GameStatus::InProgress => None,
+    }
   }
 }
 
-// #[cfg(test)]
-// mod tests {
-//   use crate::solver::*;
-
-//   #[test]
-//   fn ai_move() {
-//     let b = CompactBoard::new(6, vec![
-//       Player{pos:Pos{row:1,col:1},team:Team::Red},
-//       Player{pos:Pos{row:2,col:2},team:Team::Blue},
-//     ]);
-//     let mut a = Amazons::from_board(b);
-//     println!("{:?}", a.ai_move(Team::Red));
-//     assert_eq!(2 + 2, 4);
-//   }
-// }
+/// Teams on `board` not in `out`, in turn order.
+fn alive_teams(board: &Board, out: &[(usize, Team)]) -> Vec<Team> {
+  board.teams().into_iter().filter(|t| !out.iter().any(|(_, o)| o == t)).collect()
+}
+
+/// The team to move after `team` has moved, at `ply`, see `begin_turn`.
+fn pass_turn(board: &Board, out: &mut Vec<(usize, Team)>, ply: usize, team: Team) -> Team {
+  let alive = alive_teams(board, out);
+  let i = alive.iter().position(|t| *t == team).unwrap();
+  begin_turn(board, out, ply, alive[(i + 1) % alive.len()])
+}
+
+/// The team to move when `team`'s turn comes at `ply`: `team`, or if
+/// it has no move the next, after putting it in `out`, until one can
+/// move or only one team is left.
+fn begin_turn(board: &Board, out: &mut Vec<(usize, Team)>, ply: usize, mut team: Team) -> Team {
+  let compact = CompactBoard::new(board);
+  loop {
+    let alive = alive_teams(board, out);
+    if alive.len() <= 1 || compact.successors(team).next().is_some() {
+      return team;
+    }
+    let i = alive.iter().position(|t| *t == team).unwrap();
+    out.push((ply, team));
+    team = alive[(i + 1) % alive.len()];
+  }
+}
+
+/// A move chosen without searching.
+fn unsearched(mv: CompactMove) -> SearchInfo {
+  SearchInfo {
+    pv: vec![mv],
+    score: 0,
+    depth: 0,
+    nodes: 0,
+    time_ms: 0.0,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn position_bytes_round_trip() {
+    let mut a = Amazons::new();
+    assert!(a.player_move("c3-c5/e5".parse().unwrap()).is_ok());
+    let bytes = a.position_bytes();
+    let mut b = Amazons::new();
+    assert!(b.load_position_bytes(&bytes));
+    assert_eq!(b.position_string(), a.position_string());
+  }
+
+  #[test]
+  fn position_bytes_with_side_to_move_out() {
+    let mut a = Amazons::new();
+    let mut bytes = a.position_bytes();
+    bytes[0] |= 16 << Team::Red.index();
+    assert!(!a.load_position_bytes(&bytes));
+    assert_eq!(a.status(), GameStatus::InProgress);
+    assert!(a.suggest_move().is_some());
+  }
+
+  #[test]
+  fn hints_leave_mcts_moves_alone() {
+    let config = EngineConfig { engine: Engine::Mcts(mcts::MctsParams { iterations: 200, ..Default::default() }), ..EngineConfig::default() };
+    let mut moves = vec![];
+    for hint in [false, true].iter() {
+      let mut a = Amazons::new();
+      a.set_config(config);
+      if *hint {
+        assert!(a.suggest_move().is_some());
+      }
+      moves.push(a.ai_move().unwrap().pv[0].clone());
+    }
+    assert!(moves[0] == moves[1]);
+  }
+
+  #[test]
+  fn self_play_without_arrows_stops() {
+    let mut a = Amazons::new();
+    assert!(a.new_game(6, 6, 2, 2, Rules { arrows: 0, ..Rules::default() }));
+    let config = EngineConfig { depth: 1, evaluator: Evaluator::Territory, ..EngineConfig::default() };
+    assert_eq!(a.self_play(&[config]), None);
+    assert_eq!(a.ply(), MAX_SELF_PLAY_PLIES);
+  }
+  #[test]
+  fn delete_undone_line() {
+    let mut a = Amazons::new();
+    let start = a.position_string();
+    a.player_move("c3-c5/e5".parse().unwrap()).unwrap();
+    a.player_move("c6-b6/b3".parse().unwrap()).unwrap();
+    assert!(a.undo_move());
+    assert!(a.undo_move());
+    assert!(a.delete_variation(0));
+    assert_eq!(a.position_string(), start);
+    assert_eq!(a.turn, Team::Red);
+    assert!(!a.redo_move());
+    assert!(a.game_record(vec![]).moves.is_empty());
+  }
+}
@@ -0,0 +1,351 @@
+use crate::board::*;
+use crate::rules::*;
+
+use std::fmt;
+use std::str::FromStr;
+
+// Squares are written as in standard Amazons notation: a column
+// letter from `a` and a row number from 1, as in `d1-d7/g7`.
+// Row 1 is Red's home row, drawn at the bottom as in standard
+// diagrams, where Red is White, so rows count up from the bottom.
+//
+// The walls around the board take row and column 0, so the
+// letters and numbers map straight onto `Pos` with no offset.
+
+/// Why text is not a square or move, from `FromStr`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum NotationError {
+  /// Not a column letter followed by a row number.
+  BadSquare(String),
+  /// Not two squares joined by `-`, then a `/` before each arrow.
+  BadMove(String),
+  /// Not a position string, for the reason given.
+  BadPosition(String),
+  /// A position string for a position that cannot be played from.
+  Unplayable(PositionError),
+}
+
+impl From<PositionError> for NotationError {
+  fn from(e: PositionError) -> NotationError {
+    NotationError::Unplayable(e)
+  }
+}
+
+impl fmt::Display for NotationError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      NotationError::BadSquare(s) => write!(f, "'{}' is not a square like d1", s),
+      NotationError::BadMove(s) => write!(f, "'{}' is not a move like d1-d7/g7", s),
+      NotationError::BadPosition(why) => write!(f, "not a position string: {}", why),
+      NotationError::Unplayable(e) => write!(f, "{}", e),
+    }
+  }
+}
+
+impl fmt::Display for Pos {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "{}{}", (b'a' as i8 + self.col - 1) as u8 as char, self.row)
+  }
+}
+
+impl FromStr for Pos {
+  type Err = NotationError;
+
+  fn from_str(s: &str) -> Result<Pos, NotationError> {
+    let bad = || NotationError::BadSquare(s.to_string());
+    let mut chars = s.chars();
+    let col = match chars.next() {
+      Some(c @ 'a'..='z') => c as i8 - 'a' as i8 + 1,
+      _ => return Err(bad()),
+    };
+    let rest = chars.as_str();
+    // digits only, so no signs or leading zeros
+    if !rest.starts_with(|c: char| c.is_ascii_digit() && c != '0') || !rest.chars().all(|c| c.is_ascii_digit()) {
+      return Err(bad());
+    }
+    let row: i8 = rest.parse().map_err(|_| bad())?;
+    if col as usize > MAX_BOARD_SIZE || row as usize > MAX_BOARD_SIZE {
+      return Err(bad());
+    }
+    Ok(Pos { row, col })
+  }
+}
+
+impl fmt::Display for Move {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "{}-{}", self.old_pos, self.new_pos)?;
+    for shot in self.shots.iter().flatten() {
+      write!(f, "/{}", shot)?;
+    }
+    Ok(())
+  }
+}
+
+impl FromStr for Move {
+  type Err = NotationError;
+
+  /// A move with no arrows, one or `MAX_ARROWS`. Whether it is
+  /// legal is up to `Board::check_move`.
+  fn from_str(s: &str) -> Result<Move, NotationError> {
+    let bad = || NotationError::BadMove(s.to_string());
+    let mut parts = s.split('/');
+    let (old_pos, new_pos) = match parts.next().unwrap().split('-').collect::<Vec<_>>()[..] {
+      [from, to] => (from.parse()?, to.parse()?),
+      _ => return Err(bad()),
+    };
+    let mut shots: Shots = [None; MAX_ARROWS];
+    for (i, part) in parts.enumerate() {
+      if i >= MAX_ARROWS {
+        return Err(bad());
+      }
+      shots[i] = Some(part.parse()?);
+    }
+    Ok(Move { old_pos, new_pos, shots })
+  }
+}
+
+/// A position as text, like a chess FEN: the board size, the
+/// squares, the team to move, the teams out of the game and the
+/// rules, separated by spaces. The 8x8 start with Red to move is
+///
+/// `8x8 8/8/2R2R2/8/8/2B2B2/8/8 r - 0,0,1`
+///
+/// Squares go row by row from row 1, each row from column `a`,
+/// rows separated by `/`. `R`, `B`, `G` and `Y` are pieces, `x`
+/// is an arrow and a number is that many empty squares. The teams
+/// out are their lowercase letters, or `-` for none, and the rules
+/// are the numbers of `Rules::to_bytes` joined by commas. Both may
+/// be left off when no team is out and the rules are standard.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Position {
+  pub board: Board,
+  pub turn: Team,
+  pub out: Vec<Team>,
+}
+
+fn team_letter(team: Team) -> char {
+  match team {
+    Team::Red => 'r',
+    Team::Blue => 'b',
+    Team::Green => 'g',
+    Team::Yellow => 'y',
+  }
+}
+
+fn letter_team(c: char) -> Option<Team> {
+  Team::teams().into_iter().find(|t| team_letter(*t) == c)
+}
+
+impl fmt::Display for Position {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    let (rows, cols) = (self.board.rows(), self.board.cols());
+    write!(f, "{}x{} ", rows - 2, cols - 2)?;
+    for r in 1..rows - 1 {
+      if r > 1 {
+        write!(f, "/")?;
+      }
+      let mut empty = 0;
+      for slot in &self.board.board[r][1..cols - 1] {
+        let c = match slot {
+          BoardSlot::Empty => {
+            empty += 1;
+            continue;
+          }
+          BoardSlot::Wall => 'x',
+          BoardSlot::Piece(t) => team_letter(*t).to_ascii_uppercase(),
+        };
+        if empty > 0 {
+          write!(f, "{}", empty)?;
+          empty = 0;
+        }
+        write!(f, "{}", c)?;
+      }
+      if empty > 0 {
+        write!(f, "{}", empty)?;
+      }
+    }
+    write!(f, " {} ", team_letter(self.turn))?;
+    if self.out.is_empty() {
+      write!(f, "-")?;
+    }
+    for t in &self.out {
+      write!(f, "{}", team_letter(*t))?;
+    }
+    let [move_range, arrow_range, arrows] = self.board.rules.to_bytes();
+    write!(f, " {},{},{}", move_range, arrow_range, arrows)
+  }
+}
+
+impl FromStr for Position {
+  type Err = NotationError;
+
+  fn from_str(s: &str) -> Result<Position, NotationError> {
+    let bad = |why: String| NotationError::BadPosition(why);
+    let fields: Vec<&str> = s.split_whitespace().collect();
+    if fields.len() < 3 || fields.len() > 5 {
+      return Err(bad(format!("{} fields instead of 3 to 5", fields.len())));
+    }
+
+    let size: Vec<usize> = fields[0].split('x').map(|n| n.parse().ok()).collect::<Option<_>>().unwrap_or_default();
+    let mut board = match size[..] {
+      [rows, cols] => Board::empty(rows, cols).ok_or_else(|| {
+        bad(format!("sides must be {} to {}", MIN_BOARD_SIZE, MAX_BOARD_SIZE))
+      })?,
+      _ => return Err(bad(format!("'{}' is not a size like 8x8", fields[0]))),
+    };
+    let (rows, cols) = (board.rows() - 2, board.cols() - 2);
+
+    let lines: Vec<&str> = fields[1].split('/').collect();
+    if lines.len() != rows {
+      return Err(bad(format!("{} rows instead of {}", lines.len(), rows)));
+    }
+    for (r, line) in lines.iter().enumerate() {
+      let mut c = 0;
+      let mut empty = 0;
+      // a '/' at the end counts any empty squares left
+      for ch in line.chars().chain(std::iter::once('/')) {
+        if let Some(d) = ch.to_digit(10) {
+          empty = (empty * 10 + d as usize).min(cols + 1);
+          continue;
+        }
+        c += empty;
+        empty = 0;
+        let slot = match ch {
+          '/' => break,
+          'x' => BoardSlot::Wall,
+          _ => match letter_team(ch.to_ascii_lowercase()) {
+            Some(t) if ch.is_ascii_uppercase() => BoardSlot::Piece(t),
+            _ => return Err(bad(format!("'{}' is not a piece, arrow or number", ch))),
+          },
+        };
+        if c < cols {
+          board.board[r + 1][c + 1] = slot;
+        }
+        c += 1;
+      }
+      if c != cols {
+        return Err(bad(format!("row {} has {} squares instead of {}", r + 1, c, cols)));
+      }
+    }
+
+    let mut teams = fields[2].chars().map(letter_team);
+    let turn = match (teams.next(), teams.next()) {
+      (Some(Some(t)), None) => t,
+      _ => return Err(bad(format!("'{}' is not a team to move like r", fields[2]))),
+    };
+
+    let out = match fields.get(3) {
+      None | Some(&"-") => vec![],
+      Some(letters) => letters.chars().map(letter_team).collect::<Option<Vec<Team>>>().ok_or_else(|| {
+        bad(format!("'{}' is not teams out like gy", letters))
+      })?,
+    };
+
+    if let Some(rules) = fields.get(4) {
+      let bytes: Vec<u8> = rules.split(',').map(|n| n.parse().ok()).collect::<Option<_>>().unwrap_or_default();
+      board.rules = match bytes[..] {
+        [a, b, c] => Rules::from_bytes([a, b, c]),
+        _ => None,
+      }.ok_or_else(|| bad(format!("'{}' are not rules like 0,0,1", rules)))?;
+    }
+
+    Ok(Position { board, turn, out })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn round_trip(position: Position) {
+    let text = position.to_string();
+    assert_eq!(text.parse::<Position>(), Ok(position), "{}", text);
+  }
+
+  fn start(rows: usize, cols: usize, teams: usize, amazons: usize) -> Position {
+    let board = Board::starting(rows, cols, teams, amazons).unwrap();
+    Position { board, turn: Team::Red, out: vec![] }
+  }
+
+  #[test]
+  fn standard_start() {
+    let text = "8x8 8/8/2R2R2/8/8/2B2B2/8/8 r - 0,0,1";
+    let position: Position = text.parse().unwrap();
+    assert_eq!(position, start(8, 8, 2, 2));
+    assert_eq!(position.to_string(), text);
+    // teams out and rules may be left off
+    assert_eq!("8x8 8/8/2R2R2/8/8/2B2B2/8/8 r".parse(), Ok(position));
+  }
+
+  #[test]
+  fn position_round_trips() {
+    round_trip(start(8, 8, 2, 2));
+    round_trip(start(10, 10, 2, 4));
+    round_trip(start(6, 9, 2, 2));
+
+    let mut three = start(8, 8, 3, 2);
+    *three.board.at(Pos { row: 4, col: 5 }) = BoardSlot::Wall;
+    three.turn = Team::Green;
+    three.out = vec![Team::Blue];
+    round_trip(three);
+
+    let mut variant = start(10, 10, 2, 4);
+    variant.board.rules = Rules { move_range: Some(1), arrow_range: Some(3), arrows: 2 };
+    variant.turn = Team::Blue;
+    round_trip(variant);
+  }
+
+  #[test]
+  fn bad_positions() {
+    let bad = [
+      // sizes
+      "8 8/8/2R2R2/8/8/2B2B2/8/8 r",
+      "8x 8/8/2R2R2/8/8/2B2B2/8/8 r",
+      "2x8 8/8 r",
+      "17x8 8/8/8/8/8/8/8/8/8/8/8/8/8/8/8/8/8 r",
+      // rows
+      "8x8 8/8/2R2R2/8/8/2B2B2/8 r",
+      "8x8 8/8/2R2R3/8/8/2B2B2/8/8 r",
+      "8x8 8/8/2R2R1/8/8/2B2B2/8/8 r",
+      "8x8 8/8/2R2R2/8/8/2B2Q2/8/8 r",
+      // turns
+      "8x8 8/8/2R2R2/8/8/2B2B2/8/8 q",
+      "8x8 8/8/2R2R2/8/8/2B2B2/8/8 rb",
+      "8x8 8/8/2R2R2/8/8/2B2B2/8/8 R",
+      // teams out and rules
+      "8x8 8/8/2R2R2/8/8/2B2B2/8/8 r q",
+      "8x8 8/8/2R2R2/8/8/2B2B2/8/8 r - 0,0,3",
+      "8x8 8/8/2R2R2/8/8/2B2B2/8/8 r - 0,0",
+    ];
+    for text in bad.iter() {
+      assert!(text.parse::<Position>().is_err(), "{}", text);
+    }
+  }
+
+  #[test]
+  fn square_round_trips() {
+    for &(row, col, text) in [(1, 1, "a1"), (7, 4, "d7"), (10, 10, "j10"), (16, 16, "p16")].iter() {
+      let pos = Pos { row, col };
+      assert_eq!(pos.to_string(), text);
+      assert_eq!(text.parse(), Ok(pos));
+    }
+    for text in ["", "a", "1", "a0", "a01", "a+1", "A1", "q1", "a17", "d7 "].iter() {
+      assert!(text.parse::<Pos>().is_err(), "{}", text);
+    }
+  }
+
+  #[test]
+  fn move_round_trips() {
+    for text in ["d1-d7", "d1-d7/g7", "d1-d7/g7/g4", "j10-a1/j10"].iter() {
+      let mv: Move = text.parse().unwrap();
+      assert_eq!(mv.to_string(), *text);
+    }
+    let mv: Move = "d1-d7/g7".parse().unwrap();
+    assert_eq!(mv.old_pos, Pos { row: 1, col: 4 });
+    assert_eq!(mv.new_pos, Pos { row: 7, col: 4 });
+    assert_eq!(mv.shots, [Some(Pos { row: 7, col: 7 }), None]);
+    for text in ["d1", "d1d7", "d1-d7-d8", "d1-d7/", "d1-d7/g7/g4/g3", "d1-x7/g7"].iter() {
+      assert!(text.parse::<Move>().is_err(), "{}", text);
+    }
+  }
+}
@@ -0,0 +1,177 @@
+use crate::algo::{top_n, SearchInfo, LOSS, WIN};
+use crate::board::*;
+use crate::compact_board::*;
+use crate::config::*;
+use crate::utils::now_ms;
+
+/// Best move for `team` in a game of more than two teams, by
+/// paranoid search: every other team is assumed to play against
+/// `team`, which makes the game two-sided again so alpha-beta
+/// pruning works as in `iterative_deepening`.
+///
+/// `alive` is the teams still playing in turn order, `team` among
+/// them. Scores are for `team`, by `evaluate_territory_multi`. Each
+/// team's move is a ply, and only the `beam_width` statically best
+/// moves are searched, within `config`'s depth and budgets.
+pub fn paranoid(cache: &mut DistState, board: &CompactBoard, team: Team, alive: &[Team], config: EngineConfig) -> SearchInfo {
+  let mut search = Paranoid {
+    cache,
+    config,
+    root: team,
+    pv: vec![],
+    start_ms: now_ms(),
+    nodes: 0,
+    can_abort: false,
+    aborted: false,
+  };
+  let mut result = SearchInfo { pv: vec![], score: LOSS, depth: 0, nodes: 0, time_ms: 0.0 };
+  let mover = alive.iter().position(|t| *t == team).unwrap();
+
+  for depth in 1..=config.depth.max(1) {
+    search.can_abort = depth > 1;
+    let score = search.search(board, Node { alive, mover, depth, ply: 0 }, LOSS, WIN);
+    if search.aborted {
+      break;
+    }
+    result.pv = search.pv[0].clone();
+    result.score = score;
+    result.depth = depth;
+    if result.pv.is_empty() || score == WIN || score == LOSS {
+      break;
+    }
+  }
+
+  result.nodes = search.nodes;
+  result.time_ms = now_ms() - search.start_ms;
+  result
+}
+
+/// Where a node of the paranoid search is.
+#[derive(Clone, Copy)]
+struct Node<'t> {
+  /// Teams still in the game, in turn order.
+  alive: &'t [Team],
+  /// Index in `alive` of the team to move.
+  mover: usize,
+  depth: i32,
+  /// Distance from the root.
+  ply: usize,
+}
+
+/// Scratch state shared by every node of one paranoid search.
+struct Paranoid<'a> {
+  cache: &'a mut DistState,
+  config: EngineConfig,
+  /// The team the search is for, which every other plays against.
+  root: Team,
+  /// `pv[ply]` is the best line found from the node at `ply`.
+  pv: Vec<Vec<CompactMove>>,
+  start_ms: f64,
+  nodes: u64,
+  can_abort: bool,
+  aborted: bool,
+}
+
+impl<'a> Paranoid<'a> {
+  fn evaluate(&mut self, board: &CompactBoard, alive: &[Team]) -> i64 {
+    self.nodes += 1;
+    if self.can_abort && !self.aborted && self.nodes.is_multiple_of(64) {
+      self.aborted = self.out_of_budget();
+    }
+    evaluate_territory_multi(board, self.root, alive, self.cache)
+  }
+
+  fn out_of_budget(&self) -> bool {
+    if let Some(nodes) = self.config.nodes {
+      if self.nodes >= nodes {
+        return true;
+      }
+    }
+    if let Some(time_ms) = self.config.time_ms {
+      if now_ms() - self.start_ms >= time_ms {
+        return true;
+      }
+    }
+    false
+  }
+
+  fn start_pv(&mut self, ply: usize) {
+    if self.pv.len() < ply + 2 {
+      self.pv.resize(ply + 2, vec![]);
+    }
+    self.pv[ply].clear();
+    self.pv[ply + 1].clear();
+  }
+
+  fn update_pv(&mut self, ply: usize, mv: CompactMove) {
+    let (head, tail) = self.pv.split_at_mut(ply + 1);
+    let line = &mut head[ply];
+    line.clear();
+    line.push(mv);
+    line.extend_from_slice(&tail[0]);
+  }
+
+  /// Fail-soft alpha-beta with `alive[mover]` to move. The root
+  /// team maximizes and all others minimize.
+  fn search(&mut self, board: &CompactBoard, node: Node, mut alpha: i64, mut beta: i64) -> i64 {
+    let Node { alive, mover, depth, ply } = node;
+    self.start_pv(ply);
+    if alive.len() == 1 {
+      return if alive[0] == self.root { WIN } else { LOSS };
+    }
+    let team = alive[mover];
+    let maximizing = team == self.root;
+
+    // best for the mover first
+    let mut local_board = board.clone();
+    let mut moves = top_n(self.config.beam_width, board.successors(team).map(|m| {
+      local_board.apply_move(&m);
+      let eval = self.evaluate(&local_board, alive);
+      local_board.un_apply_move(&m);
+      (if maximizing { eval } else { -eval }, m)
+    }));
+
+    if moves.is_empty() {
+      // out of the game; the next team now has its index
+      if maximizing {
+        return LOSS;
+      }
+      let rest: Vec<Team> = alive.iter().cloned().filter(|t| *t != team).collect();
+      return self.search(board, Node { alive: &rest, mover: mover % rest.len(), ..node }, alpha, beta);
+    }
+
+    if depth <= 1 {
+      let (eval, mv) = moves.remove(0);
+      self.update_pv(ply, mv);
+      return if maximizing { eval } else { -eval };
+    }
+
+    let next = (mover + 1) % alive.len();
+    let mut score = if maximizing { LOSS } else { WIN };
+    let mut found = false;
+    for (_, mv) in moves {
+      if self.aborted {
+        break;
+      }
+      local_board.apply_move(&mv);
+      let child = self.search(&local_board, Node { mover: next, depth: depth - 1, ply: ply + 1, ..node }, alpha, beta);
+      local_board.un_apply_move(&mv);
+
+      let better = if maximizing { child > score } else { child < score };
+      if !found || better {
+        found = true;
+        score = child;
+        self.update_pv(ply, mv);
+      }
+      if maximizing {
+        alpha = alpha.max(score);
+      } else {
+        beta = beta.min(score);
+      }
+      if alpha >= beta {
+        break;
+      }
+    }
+    score
+  }
+}
@@ -0,0 +1,331 @@
+use crate::board::*;
+use crate::notation::*;
+
+use std::fmt;
+use std::str::FromStr;
+
+/// A whole game written down like a chess PGN: headers in square
+/// brackets, then the moves in notation, numbered, ending with the
+/// result.
+///
+/// ```text
+/// [Red "Alice"]
+/// [Blue "Bob"]
+/// [Date "2026.10.18"]
+/// [Size "8x8"]
+/// [Position "8x8 8/8/2R2R2/8/8/2B2B2/8/8 r - 0,0,1"]
+/// [Result "*"]
+///
+/// 1. c3-c5/e5 2. c6-b6/b3 *
+/// ```
+///
+/// The `Position` header gives the start and rules, see `Position`.
+/// Without it, `Size` gives the two-team start, with four amazons
+/// each on 10x10 and two otherwise. `Result` is the winning team,
+/// or `*` if the game was not finished.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GameRecord {
+  /// Headers other than `Size`, `Position` and `Result`, in order,
+  /// such as the players and date.
+  pub headers: Vec<(String, String)>,
+  pub start: Position,
+  pub moves: Vec<Move>,
+  pub result: Option<Team>,
+}
+
+/// Why a game record cannot be loaded.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RecordError {
+  /// Line `n`, counting from 1, is not a header like `[Red "Alice"]`.
+  BadHeader(usize),
+  /// Neither a `Position` nor a `Size` header.
+  NoStart,
+  /// The `Size` or `Position` header cannot be read or played from.
+  BadStart(NotationError),
+  /// The result is not a team or `*`.
+  BadResult(String),
+  /// The `Result` header and the end of the moves disagree.
+  ResultsDiffer,
+  /// Move `n`, counting from 1, cannot be read.
+  BadMove(usize, NotationError),
+  /// Move `n` breaks the rules.
+  IllegalMove(usize, Move, MoveError),
+  /// Move `n` comes after the game was won.
+  GameOver(usize),
+  /// Played out, the moves end with this result instead, `None`
+  /// for a game still going on.
+  WrongResult(Option<Team>),
+}
+
+impl fmt::Display for RecordError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      RecordError::BadHeader(n) => write!(f, "line {} is not a header like [Red \"Alice\"]", n),
+      RecordError::NoStart => write!(f, "there is no Position or Size header"),
+      RecordError::BadStart(e) => write!(f, "bad start: {}", e),
+      RecordError::BadResult(s) => write!(f, "'{}' is not a result like Red or *", s),
+      RecordError::ResultsDiffer => write!(f, "the Result header and the end of the moves differ"),
+      RecordError::BadMove(n, e) => write!(f, "move {}: {}", n, e),
+      RecordError::IllegalMove(n, mv, e) => write!(f, "move {}, {}: {}", n, mv, e),
+      RecordError::GameOver(n) => write!(f, "move {} comes after the game is over", n),
+      RecordError::WrongResult(result) => write!(f, "the moves end in {}, not the result given", result_text(*result)),
+    }
+  }
+}
+
+/// Longest line of moves `GameRecord` writes.
+const LINE_WIDTH: usize = 80;
+
+fn result_text(result: Option<Team>) -> String {
+  match result {
+    Some(team) => format!("{:?}", team),
+    None => "*".to_string(),
+  }
+}
+
+/// Inverse of `result_text`.
+fn parse_result(s: &str) -> Result<Option<Team>, RecordError> {
+  if s == "*" {
+    return Ok(None);
+  }
+  match Team::teams().into_iter().find(|t| format!("{:?}", t) == s) {
+    Some(t) => Ok(Some(t)),
+    None => Err(RecordError::BadResult(s.to_string())),
+  }
+}
+
+impl fmt::Display for GameRecord {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    let board = &self.start.board;
+    let size = format!("{}x{}", board.rows() - 2, board.cols() - 2);
+    let result = result_text(self.result);
+    let fixed = [
+      ("Size".to_string(), size),
+      ("Position".to_string(), self.start.to_string()),
+      ("Result".to_string(), result.clone()),
+    ];
+    for (key, value) in self.headers.iter().chain(fixed.iter()) {
+      let value = value.replace('\\', "\\\\").replace('"', "\\\"");
+      writeln!(f, "[{} \"{}\"]", key, value)?;
+    }
+    writeln!(f)?;
+
+    let mut line = String::new();
+    let words = self.moves.iter()
+      .enumerate()
+      .map(|(i, mv)| format!("{}. {}", i + 1, mv))
+      .chain(std::iter::once(result));
+    for word in words {
+      if !line.is_empty() && line.len() + 1 + word.len() > LINE_WIDTH {
+        writeln!(f, "{}", line)?;
+        line.clear();
+      }
+      if !line.is_empty() {
+        line.push(' ');
+      }
+      line.push_str(&word);
+    }
+    writeln!(f, "{}", line)
+  }
+}
+
+/// The key and unescaped value of a header line like `[Red "Alice"]`.
+fn parse_header(line: &str) -> Option<(String, String)> {
+  let inner = line.strip_prefix('[')?.strip_suffix(']')?;
+  let (key, rest) = inner.split_at(inner.find(' ')?);
+  let quoted = rest.trim().strip_prefix('"')?.strip_suffix('"')?;
+  let mut value = String::new();
+  let mut chars = quoted.chars();
+  while let Some(c) = chars.next() {
+    match c {
+      '\\' => value.push(chars.next()?),
+      '"' => return None,
+      _ => value.push(c),
+    }
+  }
+  if key.is_empty() {
+    return None;
+  }
+  Some((key.to_string(), value))
+}
+
+impl FromStr for GameRecord {
+  type Err = RecordError;
+
+  /// Read a record, checking its notation but not whether its
+  /// moves are legal, which `Amazons::load_record` does.
+  fn from_str(s: &str) -> Result<GameRecord, RecordError> {
+    let mut headers = vec![];
+    let (mut size, mut position, mut result) = (None, None, None);
+    let mut lines = s.lines().enumerate().peekable();
+    while let Some((i, line)) = lines.peek() {
+      let line = line.trim();
+      if line.is_empty() {
+        lines.next();
+        continue;
+      }
+      if !line.starts_with('[') {
+        break;
+      }
+      let (key, value) = parse_header(line).ok_or(RecordError::BadHeader(i + 1))?;
+      match key.as_str() {
+        "Size" => size = Some(value),
+        "Position" => position = Some(value),
+        "Result" => result = Some(parse_result(&value)?),
+        _ => headers.push((key, value)),
+      }
+      lines.next();
+    }
+
+    let start = match (position, size) {
+      (Some(p), size) => {
+        let start: Position = p.parse().map_err(RecordError::BadStart)?;
+        let board = &start.board;
+        let actual = format!("{}x{}", board.rows() - 2, board.cols() - 2);
+        if size.is_some_and(|s| s != actual) {
+          let why = format!("the Size is not the Position's {}", actual);
+          return Err(RecordError::BadStart(NotationError::BadPosition(why)));
+        }
+        start
+      }
+      (None, Some(size)) => {
+        let sides: Vec<usize> = size.split('x').map(|n| n.parse().ok()).collect::<Option<_>>().unwrap_or_default();
+        let board = match sides[..] {
+          [10, 10] => Board::starting(10, 10, 2, 4),
+          [rows, cols] => Board::starting(rows, cols, 2, 2),
+          _ => None,
+        };
+        let why = format!("'{}' is not a board size like 8x8", size);
+        let board = board.ok_or(RecordError::BadStart(NotationError::BadPosition(why)))?;
+        Position { board, turn: Team::Red, out: vec![] }
+      }
+      (None, None) => return Err(RecordError::NoStart),
+    };
+
+    let mut moves = vec![];
+    let mut ended = None;
+    for word in lines.flat_map(|(_, line)| line.split_whitespace()) {
+      if ended.is_some() {
+        return Err(RecordError::BadMove(moves.len() + 1, NotationError::BadMove(word.to_string())));
+      }
+      // move numbers are only for reading
+      if word.ends_with('.') && word[..word.len() - 1].chars().all(|c| c.is_ascii_digit()) {
+        continue;
+      }
+      if !word.contains('-') {
+        ended = Some(parse_result(word)?);
+        continue;
+      }
+      moves.push(word.parse().map_err(|e| RecordError::BadMove(moves.len() + 1, e))?);
+    }
+    if let (Some(ended), Some(result)) = (ended, result) {
+      if ended != result {
+        return Err(RecordError::ResultsDiffer);
+      }
+    }
+
+    Ok(GameRecord {
+      headers,
+      start,
+      moves,
+      result: result.or(ended).flatten(),
+    })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use super::super::{Amazons, GameStatus};
+
+  const GAME: &str = "[Red \"Alice\"]
+[Event \"Say \\\"hi\\\" \\\\o/\"]
+[Size \"8x8\"]
+[Position \"8x8 8/8/2R2R2/8/8/2B2B2/8/8 r - 0,0,1\"]
+[Result \"*\"]
+
+1. c3-c5/e5 2. c6-b6/b3 *
+";
+
+  #[test]
+  fn round_trip() {
+    let record: GameRecord = GAME.parse().unwrap();
+    assert_eq!(record.headers, vec![
+      ("Red".to_string(), "Alice".to_string()),
+      ("Event".to_string(), "Say \"hi\" \\o/".to_string()),
+    ]);
+    assert_eq!(record.moves.len(), 2);
+    assert_eq!(record.result, None);
+    assert_eq!(record.to_string(), GAME);
+  }
+
+  #[test]
+  fn long_games_wrap() {
+    let mut a = Amazons::new();
+    let config = crate::config::EngineConfig { depth: 1, ..Default::default() };
+    let winner = a.self_play(&[config]);
+    let record = a.game_record(vec![]);
+    assert!(winner.is_some());
+    assert_eq!(record.result, winner);
+    let text = record.to_string();
+    assert!(text.lines().all(|line| line.len() <= LINE_WIDTH));
+    assert_eq!(text.parse(), Ok(record));
+  }
+
+  #[test]
+  fn size_without_position() {
+    let record: GameRecord = "[Size \"10x10\"]\n1. d1-d7/g7".parse().unwrap();
+    assert_eq!(record.start.board, Board::starting(10, 10, 2, 4).unwrap());
+    assert_eq!(record.start.turn, Team::Red);
+  }
+
+  #[test]
+  fn read_errors() {
+    let cases = [
+      ("[Red Alice]", RecordError::BadHeader(1)),
+      ("\n[Red \"Alice\"]\n[Red \"A\"lice\"]", RecordError::BadHeader(3)),
+      ("1. d1-d7/g7", RecordError::NoStart),
+      ("[Size \"8x8\"]\n[Result \"Purple\"]", RecordError::BadResult("Purple".to_string())),
+      ("[Size \"8x8\"]\n[Result \"Red\"]\n1. c3-c5/e5 Blue", RecordError::ResultsDiffer),
+      ("[Size \"8x8\"]\n1. c3-c5/e5 2. c6-c0/b3", RecordError::BadMove(2, NotationError::BadSquare("c0".to_string()))),
+      ("[Size \"8x8\"]\n1. c3-c5/e5 * 2. c6-b6/b3", RecordError::BadMove(2, NotationError::BadMove("2.".to_string()))),
+    ];
+    for (text, error) in cases.iter() {
+      assert_eq!(text.parse::<GameRecord>(), Err(error.clone()), "{}", text);
+    }
+    assert!(matches!("[Size \"9x\"]".parse::<GameRecord>(), Err(RecordError::BadStart(_))));
+    assert!(matches!("[Size \"10x10\"]\n[Position \"8x8 8/8/2R2R2/8/8/2B2B2/8/8 r\"]".parse::<GameRecord>(), Err(RecordError::BadStart(_))));
+  }
+
+  /// Load `text` into a game with one move played, which must
+  /// fail with `error` and leave the game as it was.
+  fn load_fails(text: &str, error: RecordError) {
+    let mut a = Amazons::new();
+    a.player_move("f3-f4/f3".parse().unwrap()).unwrap();
+    let (position, record) = (a.position_string(), a.game_record(vec![]));
+
+    let loaded = a.load_record(&text.parse().unwrap());
+    assert_eq!(loaded, Err(error), "{}", text);
+    assert_eq!(a.position_string(), position);
+    assert_eq!(a.game_record(vec![]), record);
+    assert!(a.undo_move());
+    assert!(!a.undo_move());
+  }
+
+  #[test]
+  fn load_errors() {
+    let c3_c7: Move = "c3-c7/c8".parse().unwrap();
+    load_fails("[Size \"8x8\"]\n1. c3-c5/e5 2. c3-c7/c8", RecordError::IllegalMove(2, c3_c7, MoveError::NotYourPiece));
+    load_fails("[Size \"8x8\"]\n1. c3-c5/e5 Red", RecordError::WrongResult(None));
+  }
+
+  #[test]
+  fn load_finished_game() {
+    // Blue on a6 is shut in once Red shoots a5
+    let text = "[Position \"6x6 R5/6/6/6/1x4/Bx4 r\"]\n1. a1-a4/a5 Red";
+    let mut a = Amazons::new();
+    assert_eq!(a.load_record(&text.parse().unwrap()), Ok(()));
+    assert_eq!(a.status(), GameStatus::Winner(Team::Red));
+    assert_eq!(a.game_record(vec![]).to_string().lines().last(), Some("1. a1-a4/a5 Red"));
+  }
+}
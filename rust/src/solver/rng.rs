@@ -0,0 +1,30 @@
+/// Small deterministic xorshift64* generator, so searches can be
+/// reproduced from a seed without pulling in `rand` for wasm.
+#[derive(Clone, Debug)]
+pub struct Rng {
+  state: u64,
+}
+
+impl Rng {
+  pub fn new(seed: u64) -> Rng {
+    // xorshift gets stuck on 0
+    Rng { state: (seed ^ 0x9E37_79B9_7F4A_7C15) | 1 }
+  }
+
+  pub fn next_u64(&mut self) -> u64 {
+    self.state ^= self.state >> 12;
+    self.state ^= self.state << 25;
+    self.state ^= self.state >> 27;
+    self.state.wrapping_mul(0x2545_F491_4F6C_DD1D)
+  }
+
+  /// Uniform in `[0, 1)`.
+  pub fn unit(&mut self) -> f64 {
+    (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+  }
+
+  /// Uniform in `[0, n)`. `n` must not be 0.
+  pub fn below(&mut self, n: usize) -> usize {
+    (self.next_u64() % n as u64) as usize
+  }
+}
@@ -0,0 +1,116 @@
+use crate::board::*;
+use crate::compact_board::CompactBoard;
+
+/// Most arrows any variant shoots in one turn.
+pub const MAX_ARROWS: usize = 2;
+
+/// Arrows of one move, the first `Rules::arrows` of them set.
+pub type Shots = [Option<Pos>; MAX_ARROWS];
+
+/// How pieces and arrows move in a variant of the game. Every game
+/// keeps its own with its `Board`, and both move validation and
+/// the AI follow them.
+///
+/// The default is the standard game: pieces and arrows go any
+/// distance like chess queens, one arrow per turn.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Rules {
+  /// Farthest a piece moves along its line, None for any
+  /// distance. With 1 pieces move like chess kings.
+  pub move_range: Option<u8>,
+  /// Farthest an arrow flies, None for any distance.
+  pub arrow_range: Option<u8>,
+  /// Arrows shot from where the piece lands, each one blocking
+  /// the next, up to `MAX_ARROWS`. With none only the pieces
+  /// ever block, so games need not end.
+  pub arrows: usize,
+}
+
+impl Default for Rules {
+  fn default() -> Rules {
+    Rules {
+      move_range: None,
+      arrow_range: None,
+      arrows: 1,
+    }
+  }
+}
+
+impl Rules {
+  /// Move range, arrow range and arrows, ranges 0 for any distance.
+  pub fn to_bytes(self) -> [u8; 3] {
+    [self.move_range.unwrap_or(0), self.arrow_range.unwrap_or(0), self.arrows as u8]
+  }
+
+  /// Inverse of `to_bytes`, None for more than `MAX_ARROWS` or
+  /// ranges longer than `MAX_BOARD_SIZE`, which no board needs.
+  pub fn from_bytes(bytes: [u8; 3]) -> Option<Rules> {
+    let range = |b: u8| if b == 0 { None } else { Some(b) };
+    let too_far = |b: u8| b as usize > MAX_BOARD_SIZE;
+    if too_far(bytes[0]) || too_far(bytes[1]) || bytes[2] as usize > MAX_ARROWS {
+      return None;
+    }
+    Some(Rules {
+      move_range: range(bytes[0]),
+      arrow_range: range(bytes[1]),
+      arrows: bytes[2] as usize,
+    })
+  }
+
+  /// Squares a piece at `from` can move to.
+  pub fn piece_moves<'a>(self, board: &'a CompactBoard, from: Pos) -> impl Iterator<Item = Pos> + 'a {
+    line_range(board, from, from, self.move_range)
+  }
+
+  /// Squares an arrow shot from `from` can land on, treating
+  /// `blank`, where the piece came from, as empty.
+  pub fn arrow_shots<'a>(self, board: &'a CompactBoard, from: Pos, blank: Pos) -> impl Iterator<Item = Pos> + 'a {
+    line_range(board, from, blank, self.arrow_range)
+  }
+
+  /// Every way to shoot `arrows` arrows from `from`. Two arrows come
+  /// once per pair of squares, the farther first when they share a
+  /// line, so that the first never blocks the second.
+  pub fn shot_sets<'a>(self, board: &'a CompactBoard, from: Pos, blank: Pos) -> impl Iterator<Item = Shots> + 'a {
+    let only = |n: usize| if self.arrows == n { usize::MAX } else { 0 };
+    let none = std::iter::once([None, None]).take(only(0));
+    let one = self.arrow_shots(board, from, blank).take(only(1)).map(|a| [Some(a), None]);
+    // lines are walked outwards, so earlier squares are nearer
+    let two = self.arrow_shots(board, from, blank).take(only(2)).enumerate().flat_map(move |(i, far)| {
+      self.arrow_shots(board, from, blank).take(i).map(move |near| [Some(far), Some(near)])
+    });
+    none.chain(one).chain(two)
+  }
+
+  /// Whether a piece on `board` can move from `start` to `end`.
+  pub fn can_move(self, board: &mut Board, start: Pos, end: Pos) -> bool {
+    within(start, end, self.move_range) && board.open_line_along(start, end)
+  }
+
+  /// Whether an arrow on `board` can fly from `start` to `end`.
+  pub fn can_shoot(self, board: &mut Board, start: Pos, end: Pos) -> bool {
+    within(start, end, self.arrow_range) && board.open_line_along(start, end)
+  }
+}
+
+/// Whether `end` is at most `range` squares from `start` along
+/// a line, counting diagonal steps as one.
+fn within(start: Pos, end: Pos, range: Option<u8>) -> bool {
+  let dist = (start.row - end.row).abs().max((start.col - end.col).abs());
+  range.is_none_or(|r| dist as i16 <= r as i16)
+}
+
+const QUEEN_DIRS: [(i8,i8); 8] = [(-1,-1),(-1,0),(-1,1),
+                                  ( 0,-1)       ,( 0,1),
+                                  ( 1,-1),( 1,0),( 1,1)];
+
+/// Squares reachable from `from` along queen lines within `range`,
+/// treating `blank` as empty even if it is occupied.
+pub fn line_range<'a>(board: &'a CompactBoard, from: Pos, blank: Pos, range: Option<u8>) -> impl Iterator<Item = Pos> + 'a {
+  let range = range.map_or(i8::MAX, |r| r.min(i8::MAX as u8) as i8);
+  QUEEN_DIRS.iter().flat_map(move |dir| {
+    (1..=range)
+      .map(move |dist| from.with_offset(*dir, dist))
+      .take_while(move |place| !board.wall_at(*place) || *place == blank)
+  })
+}
@@ -0,0 +1,79 @@
+use crate::compact_board::*;
+
+/// How a stored score relates to the true score.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Bound {
+  /// The score is exact.
+  Exact,
+  /// The search failed high, the true score is at least this.
+  Lower,
+  /// The search failed low, the true score is at most this.
+  Upper,
+}
+
+/// What a search learned about one position.
+#[derive(Clone, Debug)]
+pub struct TtEntry {
+  pub key: u64,
+  pub depth: i32,
+  pub bound: Bound,
+  pub score: i64,
+  pub best: Option<CompactMove>,
+}
+
+/// Fixed-size hash table of searched positions, indexed by
+/// `CompactBoard::hash` mixed with `zobrist_turn` and
+/// `EngineConfig::evaluation_key`.
+pub struct TranspositionTable {
+  entries: Vec<Option<TtEntry>>,
+}
+
+impl TranspositionTable {
+  /// A table using at most `bytes` of memory, rounded down to
+  /// a power of two number of entries.
+  pub fn with_memory(bytes: usize) -> TranspositionTable {
+    let wanted = (bytes / std::mem::size_of::<Option<TtEntry>>()).max(1);
+    let mut len = 1;
+    while len * 2 <= wanted {
+      len *= 2;
+    }
+    TranspositionTable {
+      entries: vec![None; len],
+    }
+  }
+
+  pub fn clear(&mut self) {
+    for e in self.entries.iter_mut() {
+      *e = None;
+    }
+  }
+
+  fn slot(&self, key: u64) -> usize {
+    (key as usize) & (self.entries.len() - 1)
+  }
+
+  pub fn probe(&self, key: u64) -> Option<&TtEntry> {
+    match &self.entries[self.slot(key)] {
+      Some(e) if e.key == key => Some(e),
+      _ => None,
+    }
+  }
+
+  /// Record a result, keeping a deeper result for the same
+  /// position but always replacing other positions.
+  pub fn store(&mut self, key: u64, depth: i32, bound: Bound, score: i64, best: Option<CompactMove>) {
+    let slot = self.slot(key);
+    if let Some(old) = &self.entries[slot] {
+      if old.key == key && old.depth > depth {
+        return;
+      }
+    }
+    self.entries[slot] = Some(TtEntry {
+      key,
+      depth,
+      bound,
+      score,
+      best,
+    });
+  }
+}
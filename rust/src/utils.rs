@@ -8,3 +8,32 @@ pub fn set_panic_hook() {
   #[cfg(feature = "console_error_panic_hook")]
   console_error_panic_hook::set_once();
 }
+
+#[cfg(target_arch = "wasm32")]
+mod js_date {
+  use wasm_bindgen::prelude::*;
+
+  #[wasm_bindgen]
+  extern "C" {
+    #[wasm_bindgen(js_namespace = Date)]
+    pub fn now() -> f64;
+  }
+}
+
+/// Wall-clock milliseconds, only meaningful as a difference.
+///
+/// `std::time` panics on wasm32-unknown-unknown, so ask JS instead.
+#[cfg(target_arch = "wasm32")]
+pub fn now_ms() -> f64 {
+  js_date::now()
+}
+
+/// Wall-clock milliseconds, only meaningful as a difference.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn now_ms() -> f64 {
+  use std::time::{SystemTime, UNIX_EPOCH};
+  match SystemTime::now().duration_since(UNIX_EPOCH) {
+    Ok(d) => d.as_secs_f64() * 1000.0,
+    Err(_) => 0.0,
+  }
+}
@@ -12,26 +12,35 @@ import * as wasm from "amazons-ai-webassembly";
 export function drawWasmTiles(c2d, state, tilesize) {
   let checker_colors = ["#eae8ea", "#c1c1c1"]
 
-  for (let y = 1; y <= state.size(); y++) {
-    for (let x = 1; x <= state.size(); x++) {
+  let rows = state.rows();
+  for (let y = 1; y <= rows; y++) {
+    // row 1, Red's home row, at the bottom as in standard diagrams
+    let sy = rows + 1 - y;
+    for (let x = 1; x <= state.cols(); x++) {
       c2d.fillStyle = checker_colors[(x + y) % 2]
-      c2d.fillRect((x - 1) * tilesize, (y - 1) * tilesize, tilesize, tilesize)
+      c2d.fillRect((x - 1) * tilesize, (sy - 1) * tilesize, tilesize, tilesize)
 
       let at = state.token(y, x);
 
+      if (at.hint == true) {
+        c2d.strokeStyle = "#e0b000";
+        c2d.lineWidth = tilesize * 0.08;
+        c2d.strokeRect((x - 1 + 0.06) * tilesize, (sy - 1 + 0.06) * tilesize, tilesize * 0.88, tilesize * 0.88);
+      }
+
       if (at.wall == true) {
         c2d.fillStyle = team_color('block', at.hover);
-        drawOneShape(c2d, tilesize, y, x, "block")
+        drawOneShape(c2d, tilesize, sy, x, "block")
       }
 
       if (at.piece != null) {
         if (at.piece == wasm.DrawableTeam.Gray) {
           c2d.fillStyle = team_color('block', at.hover);
-          drawOneShape(c2d, tilesize, y, x, "circle")
+          drawOneShape(c2d, tilesize, sy, x, "circle")
         } else {
 
           c2d.fillStyle = 'white';
-          drawOneShape(c2d, tilesize, y, x, "circle outline")
+          drawOneShape(c2d, tilesize, sy, x, "circle outline")
 
           if (at.piece == wasm.DrawableTeam.Red) {
             c2d.fillStyle = team_color('red', at.hover);
@@ -39,8 +48,14 @@ export function drawWasmTiles(c2d, state, tilesize) {
           if (at.piece == wasm.DrawableTeam.Blue) {
             c2d.fillStyle = team_color('blue', at.hover);
           }
+          if (at.piece == wasm.DrawableTeam.Green) {
+            c2d.fillStyle = team_color('green', at.hover);
+          }
+          if (at.piece == wasm.DrawableTeam.Yellow) {
+            c2d.fillStyle = team_color('yellow', at.hover);
+          }
 
-          drawOneShape(c2d, tilesize, y, x, "circle")
+          drawOneShape(c2d, tilesize, sy, x, "circle")
 
 
         }
@@ -49,6 +64,32 @@ export function drawWasmTiles(c2d, state, tilesize) {
       at.free();
     }
   }
+  drawLabels(c2d, state, tilesize);
+}
+
+/**
+ * Row numbers down the left edge and column letters along the
+ * bottom, as in move notation, so typed moves can be found.
+ *
+ * @param {CanvasRenderingContext2D} c2d
+ * @param {wasm.State} state
+ * @param {number} tilesize
+ */
+function drawLabels(c2d, state, tilesize) {
+  let rows = state.rows();
+  c2d.fillStyle = "#606060";
+  c2d.font = Math.round(tilesize * 0.2) + "px sans-serif";
+  c2d.textAlign = "left";
+  c2d.textBaseline = "top";
+  for (let y = 1; y <= rows; y++) {
+    c2d.fillText(String(y), 0.05 * tilesize, (rows - y + 0.05) * tilesize);
+  }
+  c2d.textAlign = "right";
+  c2d.textBaseline = "bottom";
+  for (let x = 1; x <= state.cols(); x++) {
+    let letter = String.fromCharCode("a".charCodeAt(0) + x - 1);
+    c2d.fillText(letter, (x - 0.05) * tilesize, (rows - 0.05) * tilesize);
+  }
 }
 
 
@@ -108,5 +149,21 @@ function team_color(team_name, faded = false) {
       return "#0000ff"
     }
   }
+
+  if (team_name == "green") {
+    if (faded) {
+      return "#00a00066"
+    } else {
+      return "#00a000"
+    }
+  }
+
+  if (team_name == "yellow") {
+    if (faded) {
+      return "#e0c00066"
+    } else {
+      return "#e0c000"
+    }
+  }
   throw new Error("Unkown team: " + team_name)
 }
@@ -0,0 +1,48 @@
+// @ts-check
+/* jshint -W069, esversion:6 */
+
+/** Whether the AI can run in `worker.js`. */
+export function workersAvailable() {
+  return typeof Worker !== "undefined";
+}
+
+/**
+ * Promise-based wrapper around the AI running in `worker.js`.
+ */
+export class BackgroundEngine {
+  constructor() {
+    this.worker = new Worker("./worker.js");
+    this.nextId = 0;
+    /** @type {Map<number, (result: {move: Int8Array, info: any}) => void>} */
+    this.waiting = new Map();
+
+    this.worker.onmessage = (event) => {
+      let resolve = this.waiting.get(event.data.id);
+      this.waiting.delete(event.data.id);
+      if (resolve) {
+        resolve({ move: event.data.move, info: event.data.info });
+      }
+    };
+  }
+
+  /** Whether a search is still running. */
+  busy() {
+    return this.waiting.size > 0;
+  }
+
+  /**
+   * Search `position` (from `State.position()`) in the background.
+   *
+   * @param {Uint8Array} position
+   * @param {string} options AI settings from `State.engine_options()`
+   * @returns {Promise<{move: Int8Array, info: any}>} the move for `State.play_move()`,
+   *   empty if there is none, and the search's `SearchReport` fields or null
+   */
+  think(position, options) {
+    let id = this.nextId++;
+    return new Promise(resolve => {
+      this.waiting.set(id, resolve);
+      this.worker.postMessage({ id: id, position: position, options: options });
+    });
+  }
+}
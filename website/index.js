@@ -3,6 +3,7 @@
 
 import * as wasm from "amazons-ai-webassembly";
 import { drawWasmTiles } from "./drawstate.js";
+import { BackgroundEngine, workersAvailable } from "./engine.js";
 
 /** @type {((dt: number, totaltime: number) => void)[]} */
 let animations = [];
@@ -17,6 +18,28 @@ let animations = [];
   let next2go_2d = next2go.getContext("2d");
 
   let state = wasm.State.new();
+  let engine = workersAvailable() ? new BackgroundEngine() : null;
+  // without workers the AI thinks a few positions per frame instead
+  let stepping = false;
+  let thinkingNow = () => stepping || (engine !== null && engine.busy());
+
+  /** @type {HTMLSpanElement} */
+  let thinking = (document.getElementById("thinking"));
+  /** @type {HTMLSpanElement} */
+  let winner = (document.getElementById("winner"));
+  let gameOver = () => state.winner() !== undefined;
+  let teamName = (team) => wasm.DrawableTeam[team].toLowerCase();
+  /** @type {HTMLSpanElement} */
+  let analysis = (document.getElementById("analysis"));
+  let showSearch = function (info) {
+    if (!info) {
+      analysis.textContent = "";
+      return;
+    }
+    analysis.textContent = "depth " + info.depth + ", score " + info.score +
+      ", " + info.nodes + " positions in " + Math.round(info.time_ms) + "ms, expecting " +
+      (info.pv.length / 8) + " moves";
+  }
 
   /** @type {HTMLButtonElement} */
   let undo = (document.getElementById("undo"));
@@ -24,45 +47,295 @@ let animations = [];
     state.undo();
   }
   /** @type {HTMLButtonElement} */
+  let redo = (document.getElementById("redo"));
+  redo.onmousedown = function () {
+    state.redo();
+  }
+  /** @type {HTMLButtonElement} */
+  let first = (document.getElementById("first"));
+  first.onmousedown = function () {
+    state.first();
+  }
+  /** @type {HTMLButtonElement} */
+  let last = (document.getElementById("last"));
+  last.onmousedown = function () {
+    state.last();
+  }
+  /** @type {HTMLSpanElement} */
+  let ply = (document.getElementById("ply"));
+  /** @type {HTMLButtonElement} */
   let newgame = (document.getElementById("newgame"));
+  /** @type {HTMLSelectElement} */
+  let setup = (document.getElementById("setup"));
+  /** @type {HTMLSelectElement} */
+  let rules = (document.getElementById("rules"));
   newgame.onmousedown = function () {
-    state.new_game();
+    if (thinkingNow()) {
+      return;
+    }
+    let [moveRange, arrowRange, arrows] = rules.value.split("x").map(Number);
+    state.set_rules(moveRange, arrowRange, arrows);
+    let [rows, cols, teams, amazons] = setup.value.split("x").map(Number);
+    state.new_game(rows, cols, teams, amazons);
   }
+  /** @type {HTMLSelectElement} */
+  let difficultySelect = (document.getElementById("difficulty"));
+  let difficulty = () => wasm.DifficultyLevel[difficultySelect.value];
+  difficultySelect.onchange = function () {
+    state.set_difficulty(difficulty());
+  }
+  state.set_difficulty(difficulty());
+
   /** @type {HTMLButtonElement} */
   let makeai = (document.getElementById("makeai"));
   makeai.onmousedown = function (event) {
-    state.ai_move();
+    if (thinkingNow() || gameOver() || state.editing()) {
+      return;
+    }
+    if (engine === null) {
+      state.begin_ai_move();
+      stepping = true;
+      return;
+    }
+    let position = state.position();
+    engine.think(position, state.engine_options()).then(result => {
+      // ignore the answer if the board changed while thinking
+      if (state.position().join() == position.join() && result.move.length > 0) {
+        try {
+          state.play_move(result.move);
+          showSearch(result.info);
+        } catch (e) {
+          console.error("AI move rejected:", e);
+        }
+      }
+    });
+  }
+
+  /** @type {HTMLButtonElement} */
+  let hint = (document.getElementById("hint"));
+  hint.onmousedown = function (event) {
+    if (thinkingNow() || gameOver() || state.editing()) {
+      return;
+    }
+    let suggested = state.hint();
+    if (suggested !== undefined) {
+      suggested.free();
+    }
+  }
+
+  /** @type {HTMLInputElement} */
+  let notation = (document.getElementById("notation"));
+  /** @type {HTMLSpanElement} */
+  let notationError = (document.getElementById("notationerror"));
+  document.getElementById("playnotation").onmousedown = function () {
+    if (thinkingNow() || state.editing()) {
+      return;
+    }
+    try {
+      state.play_notation(notation.value);
+      notation.value = "";
+      notationError.textContent = "";
+    } catch (e) {
+      notationError.textContent = e;
+    }
   }
 
+  /** @type {HTMLInputElement} */
+  let positionText = (document.getElementById("positiontext"));
+  /** @type {HTMLSpanElement} */
+  let positionError = (document.getElementById("positionerror"));
+  document.getElementById("getposition").onmousedown = function () {
+    positionText.value = state.position_string();
+    positionError.textContent = "";
+  }
+  document.getElementById("loadposition").onmousedown = function () {
+    if (thinkingNow() || state.editing()) {
+      return;
+    }
+    try {
+      state.load_position(positionText.value);
+      showSearch(null);
+      positionError.textContent = "";
+    } catch (e) {
+      positionError.textContent = e;
+    }
+  }
+
+  /** @type {HTMLTextAreaElement} */
+  let recordText = (document.getElementById("recordtext"));
+  /** @type {HTMLSpanElement} */
+  let recordError = (document.getElementById("recorderror"));
+  document.getElementById("saverecord").onmousedown = function () {
+    if (thinkingNow()) {
+      return;
+    }
+    let today = new Date();
+    state.set_record_header("Date", today.getFullYear() + "." +
+      String(today.getMonth() + 1).padStart(2, "0") + "." + String(today.getDate()).padStart(2, "0"));
+    recordText.value = state.game_record();
+    recordError.textContent = "";
+  }
+  document.getElementById("loadrecord").onmousedown = function () {
+    if (thinkingNow() || state.editing()) {
+      return;
+    }
+    try {
+      state.load_game_record(recordText.value);
+      showSearch(null);
+      recordError.textContent = "";
+    } catch (e) {
+      recordError.textContent = e;
+    }
+  }
+
+  /** @type {HTMLButtonElement} */
+  let analyze = (document.getElementById("analyze"));
+  /** @type {HTMLOListElement} */
+  let candidates = (document.getElementById("candidates"));
+  analyze.onmousedown = function (event) {
+    if (thinkingNow() || state.editing()) {
+      return;
+    }
+    candidates.innerHTML = "";
+    for (let report of state.analyze(5)) {
+      let item = document.createElement("li");
+      item.textContent = report.line() + ", score " + report.score;
+      candidates.appendChild(item);
+      report.free();
+    }
+  }
+
+  /** @type {HTMLDivElement} */
+  let variations = (document.getElementById("variations"));
+  let shownFor = "";
+  // list the moves tried from this position, rebuilt when it changes
+  let showVariations = function (force) {
+    let position = state.position().join();
+    if (!force && position == shownFor) {
+      return;
+    }
+    shownFor = position;
+    variations.innerHTML = "";
+    state.variations().forEach((mv, i) => {
+      let row = document.createElement("div");
+      let play = document.createElement("button");
+      play.textContent = (i == 0 ? "main line " : "variation ") + mv.notation();
+      play.onmousedown = () => state.play_variation(i);
+      let promote = document.createElement("button");
+      promote.textContent = "promote";
+      promote.onmousedown = () => { state.promote_variation(i); showVariations(true); };
+      let remove = document.createElement("button");
+      remove.textContent = "delete";
+      remove.onmousedown = () => { state.delete_variation(i); showVariations(true); };
+      row.append(play, " ", promote, " ", remove);
+      variations.appendChild(row);
+      mv.free();
+    });
+  }
+
+  /** @type {HTMLButtonElement} */
+  let edit = (document.getElementById("edit"));
+  /** @type {HTMLSpanElement} */
+  let editor = (document.getElementById("editor"));
+  /** @type {HTMLSelectElement} */
+  let editTool = (document.getElementById("edittool"));
+  /** @type {HTMLSelectElement} */
+  let editFirst = (document.getElementById("editfirst"));
+  /** @type {HTMLSpanElement} */
+  let editError = (document.getElementById("editerror"));
+  edit.onmousedown = function () {
+    if (thinkingNow() || state.editing()) {
+      return;
+    }
+    state.begin_edit();
+    state.set_edit_tool(wasm.EditTool[editTool.value]);
+  }
+  editTool.onchange = function () {
+    state.set_edit_tool(wasm.EditTool[editTool.value]);
+  }
+  document.getElementById("editclear").onmousedown = function () {
+    state.clear_edit();
+  }
+  document.getElementById("editstart").onmousedown = function () {
+    try {
+      state.start_from_edit(wasm.DrawableTeam[editFirst.value]);
+      showSearch(null);
+      candidates.innerHTML = "";
+    } catch (e) {
+      console.error("cannot start from this position:", e);
+    }
+  }
+  document.getElementById("editcancel").onmousedown = function () {
+    state.cancel_edit();
+  }
+
+  // square tiles, as large as fit the canvas
+  let tileSize = () => Math.min(canvas.width / state.cols(), canvas.height / state.rows());
+
   canvas.onmouseleave = function (event) {
     state.mouse_leave();
   }
 
   // will be used for animations
   canvas.onmousemove = (function (event) {
-    let tilesize = canvas.width / state.size();
+    let tilesize = tileSize();
     let tx = Math.floor(event.offsetX / tilesize) + 1;
-    let ty = Math.floor(event.offsetY / tilesize) + 1;
+    let ty = state.rows() - Math.floor(event.offsetY / tilesize);
     state.mouse_move(ty, tx);
   })
 
   // click handler
   canvas.onmousedown = function (event) {
-    let tilesize = canvas.width / state.size();
+    if (thinkingNow()) {
+      return;
+    }
+    let tilesize = tileSize();
 
     let tx = Math.floor(event.offsetX / tilesize) + 1;
-    let ty = Math.floor(event.offsetY / tilesize) + 1;
+    // row 1 is drawn at the bottom
+    let ty = state.rows() - Math.floor(event.offsetY / tilesize);
+    if (ty < 1 || tx > state.cols()) {
+      return;
+    }
     state.mouse_click(ty, tx);
   }
 
   animations.push((dt, totaltime) => {
-    if (state.turn() == wasm.DrawableTeam.Red)
-      next2go_2d.fillStyle = "red";
-    else
-      next2go_2d.fillStyle = "blue";
+    if (stepping) {
+      let progress = state.ai_step(200);
+      stepping = progress !== undefined && !progress.done;
+      if (!stepping) {
+        let report = state.last_search();
+        showSearch(report && { nodes: report.nodes, time_ms: report.time_ms,
+          depth: report.depth, score: report.score, pv: report.pv() });
+        if (report) {
+          report.free();
+        }
+      }
+    }
+
+    next2go_2d.fillStyle = teamName(state.turn());
     next2go_2d.fillRect(0, 0, next2go.width, next2go.height)
 
-    drawWasmTiles(c2d, state, canvas.width / state.size());
+    c2d.clearRect(0, 0, canvas.width, canvas.height);
+    drawWasmTiles(c2d, state, tileSize());
+
+    thinking.style.visibility = thinkingNow() ? "visible" : "hidden";
+    ply.textContent = "move " + state.ply() + " of " + state.game_length();
+    if (!thinkingNow()) {
+      showVariations(false);
+    }
+    editor.style.display = state.editing() ? "inline" : "none";
+    let problem = state.edit_error();
+    editError.textContent = problem === undefined ? "" : "Cannot start: " + problem;
+    let won = state.winner();
+    if (won === undefined) {
+      winner.textContent = "";
+    } else {
+      let name = teamName(won);
+      winner.textContent = name[0].toUpperCase() + name.slice(1) + " wins!";
+    }
+    thinking.style.opacity = String(0.5 + 0.5 * Math.sin(totaltime * 6));
   })
 }
 
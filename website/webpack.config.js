@@ -1,7 +1,7 @@
 const CopyWebpackPlugin = require("copy-webpack-plugin");
 const path = require('path');
 
-module.exports = {
+module.exports = [{
   entry: "./bootstrap.js",
   output: {
     path: path.resolve(__dirname, "dist"),
@@ -11,4 +11,14 @@ module.exports = {
   plugins: [
     new CopyWebpackPlugin(['index.html'])
   ],
-};
+}, {
+  // the AI runs in its own bundle so it can load wasm off the main thread
+  entry: "./worker.js",
+  target: "webworker",
+  output: {
+    path: path.resolve(__dirname, "dist"),
+    filename: "worker.js",
+    chunkFilename: "worker.[id].js",
+  },
+  mode: "production",// "development",
+}];
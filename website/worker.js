@@ -0,0 +1,38 @@
+// @ts-check
+/* jshint -W069, esversion:6 */
+
+// Runs the AI off the main thread. Messages in are
+// `{ id, position, options }` and messages out are
+// `{ id, move, info }` where `move` is an Int8Array, empty if there is
+// no move, and `info` is a `SearchReport` as a plain object or null.
+
+let pending = [];
+let searcher = null;
+
+function think(msg) {
+  let move = searcher.think(msg.position, msg.options);
+  let report = searcher.last_search();
+  let info = null;
+  if (report !== undefined) {
+    info = {
+      nodes: report.nodes, time_ms: report.time_ms, depth: report.depth,
+      score: report.score, pv: report.pv(),
+    };
+    report.free();
+  }
+  postMessage({ id: msg.id, move: move, info: info });
+}
+
+self.onmessage = function (event) {
+  if (searcher === null) {
+    pending.push(event.data);
+  } else {
+    think(event.data);
+  }
+};
+
+import("amazons-ai-webassembly").then(wasm => {
+  searcher = wasm.Searcher.new();
+  pending.forEach(think);
+  pending = [];
+}).catch(e => console.error("Error importing wasm in worker:", e));